@@ -21,14 +21,16 @@ use adnl::{
 };
 #[cfg(feature = "telemetry")]
 use adnl::telemetry::Metric;
+use flate2::{Compression, read::DeflateDecoder, write::DeflateEncoder};
 use overlay::{OverlayId, OverlayShortId, OverlayUtils};
 use rand::Rng;
 use std::{
-    collections::VecDeque, convert::TryInto, fmt::{self, Display, Formatter}, 
-    sync::{Arc, atomic::{AtomicU8, AtomicU64, Ordering}}
+    collections::{HashSet, VecDeque}, convert::TryInto, fmt::{self, Display, Formatter}, 
+    io::{Read, Write}, path::{Path, PathBuf}, 
+    sync::{Arc, Mutex, RwLock, atomic::{AtomicBool, AtomicU8, AtomicU64, Ordering}}, 
+    time::{Duration, Instant}
 };
-#[cfg(feature = "telemetry")]
-use std::time::Instant;
+use tokio::sync::{mpsc, Semaphore};
 use ton_api::{
     deserialize_boxed, IntoBoxed, serialize_boxed, serialize_boxed_inplace, Signing,
     ton::{
@@ -36,7 +38,7 @@ use ton_api::{
         adnl::AddressList as AddressListBoxed, 
         dht::{
             Node as NodeBoxed, Nodes as NodesBoxed, Pong as DhtPongBoxed, Stored, UpdateRule,
-            ValueResult as DhtValueResult,
+            Value as DhtValueBoxed, ValueResult as DhtValueResult,
             key::Key as DhtKey, keydescription::KeyDescription as DhtKeyDescription, 
             node::Node, nodes::Nodes, pong::Pong as DhtPong, value::Value as DhtValue,
             valueresult::{ValueFound, ValueNotFound}
@@ -51,15 +53,20 @@ use ton_api::{
         }
     }
 };
-#[cfg(feature = "telemetry")]
 use ton_api::tag_from_boxed_type;
-use ton_types::{error, fail, base64_encode, KeyId, KeyOption, Result, UInt256};
+use ton_types::{
+    error, fail, base64_decode, base64_encode, sha256_digest, KeyId, KeyOption, Result, UInt256
+};
 
 include!("../common/src/info.rs");
 
 pub const TARGET: &str = "dht";
+pub const TARGET_SEARCH: &str = "dht::search";
+pub const TARGET_STORE: &str = "dht::store";
+pub const TARGET_TABLE: &str = "dht::table";
 
 pub struct DhtIterator {
+    fixed: bool,
     iter: Option<AddressCacheIterator>, 
     key_id: Arc<DhtKeyId>,
     order: Vec<(u8, Arc<KeyId>)>
@@ -69,6 +76,7 @@ impl DhtIterator {
 
     fn with_key_id(dht: &DhtNode, key_id: Arc<DhtKeyId>) -> Self {
         let mut ret = Self {
+            fixed: false,
             iter: None,
             key_id,
             order: Vec::new() 
@@ -77,24 +85,33 @@ impl DhtIterator {
         ret
     }
 
+    // Caller-given peers in caller order, never re-ranked or extended from table. 
+    // Own node is dropped, as in table-driven searches
+    fn with_peers(dht: &DhtNode, key_id: Arc<DhtKeyId>, peers: Vec<Arc<KeyId>>) -> Self {
+        Self {
+            fixed: true,
+            iter: None,
+            key_id,
+            order: peers
+                .into_iter()
+                .rev()
+                .filter(|peer| !dht.is_own_key(peer))
+                .map(|peer| (0, peer))
+                .collect()
+        }
+    }
+
     fn update(&mut self, dht: &DhtNode) {
+        if self.fixed {
+            return
+        }
         let mut next = if let Some(iter) = &self.iter {
             dht.known_peers.given(iter)
         } else {
             dht.get_known_peer(&mut self.iter)
         };
         while let Some(peer) = next {
-            let mut affinity = DhtNode::get_affinity(peer.data(), &self.key_id);
-            if let Some(score) = dht.bad_peers.get(&peer) {
-                let score = score.val().load(Ordering::Relaxed);
-                let new_affinity = affinity.saturating_sub(score);
-                log::debug!(
-                    target: TARGET, 
-                    "Bad DHT peer {}, score {} affinity {} -> {}", 
-                    peer, score, affinity, new_affinity
-                );
-                affinity = new_affinity;
-            }
+            let affinity = self.peer_affinity(dht, &peer);
             let add = if let Some((top_affinity, _)) = self.order.last() {
                 (*top_affinity <= affinity) || (self.order.len() < DhtNode::MAX_TASKS as usize)
             } else {
@@ -105,7 +122,70 @@ impl DhtIterator {
             }
             next = dht.get_known_peer(&mut self.iter)
         }
-        self.order.sort_unstable_by_key(|(affinity, _)| *affinity);
+        self.sort(dht)
+    }
+
+    /// Re-rank pending peers by their current health
+    fn rescore(&mut self, dht: &DhtNode) {
+        if self.fixed {
+            return
+        }
+        let mut order = std::mem::take(&mut self.order);
+        for (affinity, peer) in order.iter_mut() {
+            *affinity = self.peer_affinity(dht, peer)
+        }
+        self.order = order;
+        self.sort(dht)
+    }
+
+    /// Discard consumed order and re-rank all currently known DHT peers. 
+    /// Peers already tried may be queried again
+    pub fn rebuild(&mut self, dht: &DhtNode) {
+        *self = Self::with_key_id(dht, self.key_id.clone())
+    }
+
+    fn peer_affinity(&self, dht: &DhtNode, peer: &Arc<KeyId>) -> u8 {
+        let mut affinity = DhtNode::get_affinity(peer.data(), &self.key_id);
+        if let Some(score) = dht.bad_peers.get(peer) {
+            let score = score.val().load(Ordering::Relaxed);
+            let new_affinity = affinity.saturating_sub(score);
+            log::debug!(
+                target: TARGET_SEARCH, 
+                "Bad DHT peer {}, score {} affinity {} -> {}", 
+                peer, score, affinity, new_affinity
+            );
+            affinity = new_affinity;
+        }
+        if let Some(rtt) = dht.rtts.get(peer) {
+            let penalty = rtt.val().load(Ordering::Relaxed) / DhtNode::RTT_PENALTY_STEP;
+            let penalty = penalty.min(DhtNode::MAX_RTT_PENALTY as u64) as u8;
+            affinity = affinity.saturating_sub(penalty);
+        }
+        if let Some(helpful) = dht.helpful.get(peer) {
+            let packed = helpful.val().load(Ordering::Relaxed);
+            let score = DhtNode::helpful_score(packed, Version::get());
+            affinity = affinity.saturating_add(score.min(DhtNode::MAX_HELPFUL_BONUS as u64) as u8);
+        }
+        if dht.config.reliability_ordering {
+            if let Some(ratio) = dht.reliability.get(peer).and_then(|stats| stats.val().reliability()) {
+                let penalty = (1.0 - ratio) * DhtNode::MAX_RELIABILITY_PENALTY as f32;
+                affinity = affinity.saturating_sub(penalty as u8);
+            }
+        }
+        affinity
+    }
+
+    fn sort(&mut self, dht: &DhtNode) {
+        if dht.config.deterministic_order {
+            // Within affinity tier, peers with lower key ID are queried first
+            self.order.sort_unstable_by(
+                |(affinity1, peer1), (affinity2, peer2)| {
+                    affinity1.cmp(affinity2).then_with(|| peer2.data().cmp(peer1.data()))
+                }
+            )
+        } else {
+            self.order.sort_unstable_by_key(|(affinity, _)| *affinity)
+        }
         if let Some((top_affinity, _)) = self.order.last() {
             let mut drop_to = 0;
             while self.order.len() - drop_to > DhtNode::MAX_TASKS as usize {
@@ -123,7 +203,7 @@ impl DhtIterator {
             for (affinity, key_id) in self.order.iter().rev() {
                 out.push_str(format!("order {} - {}\n", affinity, key_id).as_str())
             }
-            log::debug!(target: TARGET, "{}", out);
+            log::debug!(target: TARGET_SEARCH, "{}", out);
         }
     }
 
@@ -182,301 +262,3169 @@ declare_counted!(
 
 #[derive(Clone)]
 pub enum DhtSearchPolicy {
-    FastSearch(u8),    // Parameter: concurrency level 
-    FullSearch(u8)     // Parameter: concurrency level
+    FastSearch(u8),         // Parameter: concurrency level 
+    FullSearch(u8),         // Parameter: concurrency level
+    LimitedSearch(u8, u32)  // Parameters: concurrency level, max search rounds
 }
 
-#[cfg(feature = "telemetry")]
-struct DhtTelemetry {
-    peers: Arc<Metric>,
-    values: Arc<Metric>
+/// Address of node found in DHT, with derived key data
+pub struct ResolvedAddress {
+    pub ip: IpAddress,
+    pub key: Arc<dyn KeyOption>,
+    pub key_id: Arc<KeyId>,
+    pub key_type: i32,
+    /// Seconds left until the address record expires
+    pub ttl_remaining: i32
 }
 
-struct DhtAlloc {
-    peers: Arc<AtomicU64>,
-    values: Arc<AtomicU64>
+impl ResolvedAddress {
+    fn with_params(ip: IpAddress, key: Arc<dyn KeyOption>, ttl: i32) -> Self {
+        Self {
+            ip,
+            key_id: key.id().clone(),
+            key_type: key.type_id(),
+            key,
+            ttl_remaining: ttl - Version::get()
+        }
+    }
 }
 
-struct OverlayNodeResolveContext {
-    node: OverlayNode,
-    key: Arc<dyn KeyOption>,
-    search: Option<AddressSearchContext>
+/// Outcome of value store: number of replicas confirmed to hold the value 
+/// against required quorum
+#[derive(Clone, Copy, Debug)]
+pub struct DhtStoreOutcome {
+    pub confirmed: usize,
+    pub quorum: usize
 }
 
-pub struct AddressSearchContext {
-    iter: Option<DhtIterator>,
-    key_id: Arc<DhtKeyId>,
+impl DhtStoreOutcome {
+    /// Check whether the value reached required number of replicas
+    pub fn is_durable(&self) -> bool {
+        self.confirmed >= self.quorum
+    }
 }
 
-pub struct OverlayNodesSearchContext {
-    key_id: Arc<DhtKeyId>,
-    search: VecDeque<OverlayNodeResolveContext>,
-    stored: AddressCache
+/// Query types served only to known sources: allowlisted ones, or peers in routing table 
+/// which have answered queries of this node. All types are open by default
+#[derive(Clone, Debug, Default)]
+pub struct DhtSourcePolicy {
+    pub allowlist: HashSet<Arc<KeyId>>,
+    pub find_value: bool,
+    pub store: bool
 }
 
-/// DHT Node
-pub struct DhtNode {
-    adnl: Arc<AdnlNode>,
-    buckets: lockfree::map::Map<u8, lockfree::map::Map<Arc<KeyId>, NodeObject>>,
-    bad_peers: lockfree::map::Map<Arc<KeyId>, AtomicU8>,
-    known_peers: AddressCache,
-    node_key: Arc<dyn KeyOption>,
-    query_prefix: Vec<u8>,
-    storage: lockfree::map::Map<DhtKeyId, ValueObject>,
-    #[cfg(feature = "telemetry")]
-    tag_dht_ping: u32,
-    #[cfg(feature = "telemetry")]
-    tag_get_signed_address_list: u32,
-    #[cfg(feature = "telemetry")]
-    tag_find_node: u32,
-    #[cfg(feature = "telemetry")]
-    tag_find_value: u32,
-    #[cfg(feature = "telemetry")]
-    tag_store: u32,
-    #[cfg(feature = "telemetry")]
-    telemetry: DhtTelemetry,
-    allocated: DhtAlloc
+/// Kind of background task spawned by DHT node
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DhtTaskKind {
+    AddressRepublisher,
+    BucketRefresh,
+    LocalNodeRefresh,
+    OverlayMembersWatch,
+    ReplicaRepair
 }
 
-impl DhtNode {
+/// Handle of background task spawned by DHT node
+pub struct DhtTaskHandle {
+    abort: tokio::task::AbortHandle,
+    id: u64,
+    kind: DhtTaskKind
+}
 
-    const BITS: [u8; 16] = [
-        4, 3, 2, 2, 1, 1, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0
-    ];
+impl DhtTaskHandle {
 
-    const MAX_FAIL_COUNT: u8 = 5;
-    const MAX_PEERS: u32 = 65536;
-    const MAX_TASKS: u8 = 5;
-    const TIMEOUT_VALUE: i32 = 3600; // Seconds
+    /// Stop the task. It stops at its next await point
+    pub fn abort(&self) {
+        self.abort.abort()
+    }
 
-    /// Constructor 
-    pub fn with_adnl_node(adnl: Arc<AdnlNode>, key_tag: usize) -> Result<Arc<Self>> {
-        let node_key = adnl.key_by_tag(key_tag)?;
-        #[cfg(feature = "telemetry")]
-        let telemetry = DhtTelemetry {
-            peers: adnl.add_metric("Alloc DHT peers"),
-            values: adnl.add_metric("Alloc DHT values")
-        };
-        let allocated = DhtAlloc {
-            peers: Arc::new(AtomicU64::new(0)),
-            values: Arc::new(AtomicU64::new(0))
-        };
-        let mut ret = Self {
-            adnl,
-            buckets: lockfree::map::Map::new(),
-            bad_peers: lockfree::map::Map::new(), 
-            known_peers: AddressCache::with_limit(Self::MAX_PEERS),
-            node_key,
-            query_prefix: Vec::new(),
-            storage: lockfree::map::Map::new(),
-            #[cfg(feature = "telemetry")]
-            tag_dht_ping: tag_from_boxed_type::<DhtPing>(),
-            #[cfg(feature = "telemetry")]
-            tag_find_node: tag_from_boxed_type::<FindNode>(),
-            #[cfg(feature = "telemetry")]
-            tag_find_value: tag_from_boxed_type::<FindValue>(),
-            #[cfg(feature = "telemetry")]
-            tag_get_signed_address_list: tag_from_boxed_type::<GetSignedAddressList>(),
-            #[cfg(feature = "telemetry")]
-            tag_store: tag_from_boxed_type::<Store>(),
-            #[cfg(feature = "telemetry")]
-            telemetry,
-            allocated
-        };
-        let query = DhtQuery { 
-            node: ret.sign_local_node()?
-        };
-        serialize_boxed_inplace(&mut ret.query_prefix, &query)?;
-        Ok(Arc::new(ret))
+    /// Task ID as listed by DhtNode::spawned_tasks
+    pub fn id(&self) -> u64 {
+        self.id
     }
 
-    /// Add DHT peer 
-    pub fn add_peer(&self, peer: &Node) -> Result<Option<Arc<KeyId>>> {
-        if let Err(e) = self.verify_other_node(peer) {
-            log::warn!(target: TARGET, "Error when verifying DHT peer: {}", e);
-            return Ok(None)
-        }
-        let addr = if let Some(addr) = AdnlNode::parse_address_list(&peer.addr_list)? {
-            addr
-        } else {
-            log::warn!(target: TARGET, "Wrong DHT peer address {:?}", peer.addr_list);
-            return Ok(None)
-        };
-        let ret = self.adnl.add_peer(
-            self.node_key.id(), 
-            &addr, 
-            &((&peer.id).try_into()?)
-        )?;
-        let ret = if let Some(ret) = ret {
-            ret
-        } else {
-            return Ok(None)
-        };
-        if self.known_peers.put(ret.clone())? {
-            let key1 = self.node_key.id().data();
-            let key2 = ret.data();
-            let affinity = Self::get_affinity(key1, key2);
-            add_unbound_object_to_map(
-                &self.buckets, 
-                affinity, 
-                || Ok(lockfree::map::Map::new())
-            )?;
-            if let Some(bucket) = self.buckets.get(&affinity) {
-                add_counted_object_to_map_with_update(
-                    bucket.val(),
-                    ret.clone(), 
-                    |old_node| {
-                        if let Some(old_node) = old_node {
-                            if old_node.object.version >= peer.version {
-                                return Ok(None)
-                            }
-                        }
-                        let ret = NodeObject {
-                            object: peer.clone(),
-                            counter: self.allocated.peers.clone().into()
-                        };
-                        #[cfg(feature = "telemetry")]
-                        self.telemetry.peers.update(
-                            self.allocated.peers.load(Ordering::Relaxed)
-                        );
-                        Ok(Some(ret))
-                    }
-                )?;
-            }
-        } else {
-            self.set_good_peer(&ret)
-        }
-        Ok(Some(ret))
+    /// Kind of spawned DHT task
+    pub fn kind(&self) -> DhtTaskKind {
+        self.kind
     }
 
-    /// Find DHT nodes
-    pub async fn find_dht_nodes(&self, dst: &Arc<KeyId>) -> Result<bool> {
-        let query = TaggedTlObject {
-            object: TLObject::new(
-                FindNode {
-                    key: UInt256::with_array(*self.node_key.id().data()),
-                    k: 10
-                }
-            ),
-            #[cfg(feature = "telemetry")]
-            tag: self.tag_find_node
-        };
-        let answer = self.query_with_prefix(dst, &query).await?;
-        let answer: NodesBoxed = if let Some(answer) = answer {
-            Query::parse(answer, &query.object)?
+}
+
+/// Typed DHT failures callers may want to tell apart, via failure::Error::downcast_ref
+#[derive(Debug, failure::Fail)]
+pub enum DhtError {
+    #[fail(display = "DHT is not bootstrapped: no known peers")]
+    NoPeers
+}
+
+/// Outcome of own IP address store
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DhtAddressStoreOutcome {
+    Stored,         // Stored and read back from network
+    Unconfirmed,    // Stored, but not found back in network
+    NoLocalAddress  // Node has no usable address yet, nothing stored, retry later
+}
+
+/// Decision on value found during value search
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DhtValueCheck {
+    Accept,        // Take value, go on searching if more are required
+    Reject,        // Drop value, go on searching
+    AcceptAndStop  // Take value as authoritative, stop search
+}
+
+impl From<bool> for DhtValueCheck {
+    fn from(accept: bool) -> Self {
+        if accept {
+            Self::Accept
         } else {
-            return Ok(false)
-        };        
-        let src = answer.only().nodes;
-        log::debug!(target: TARGET, "-------- Found DHT nodes:");
-        for node in src.iter() {
-            log::debug!(target: TARGET, "{:?}", node);
-            self.add_peer(node)?; 
+            Self::Reject
         }
-        Ok(true)
     }
+}
 
-    /// Fetch address of node (locally) with given key ID 
-    pub async fn fetch_address(
-        &self,
-        key_id: &Arc<KeyId>
-    ) -> Result<Option<(IpAddress, Arc<dyn KeyOption>)>> {
-        let key = Self::dht_key_from_key_id(key_id, "address");
-        let value = self.search_dht_key(&hash(key)?);
-        if let Some(value) = value {
-            let object = deserialize_boxed(&value.value)?;
-            Ok(Some(Self::parse_value_as_address(value.key, object)?))
-        } else {
-            Ok(None)
+/// Order of picking known nodes from buckets
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum DhtNodesOrder {
+    #[default]
+    FarFirst,   // Ascending affinity 
+    CloseFirst, // Descending affinity
+    Spread      // One node per bucket in turn
+}
+
+/// Builder of DHT values enforcing the store contract
+pub struct DhtValueBuilder {
+    key: DhtKey,
+    ttl: i32,
+    value: Vec<u8>
+}
+
+impl DhtValueBuilder {
+
+    /// Value under DHT key derived from key ID and name, with default ttl
+    pub fn with_key_id(key_id: &Arc<KeyId>, name: &str) -> Self {
+        Self {
+            key: DhtNode::dht_key_from_key_id(key_id, name),
+            ttl: Version::get() + DhtNode::TIMEOUT_VALUE,
+            value: Vec::new()
         }
     }
 
-    /// Find address of node with given key ID 
-    pub async fn find_address(
-        dht: &Arc<Self>, 
-        key_id: &Arc<KeyId>
-    ) -> Result<Option<(IpAddress, Arc<dyn KeyOption>)>> {
-        DhtNode::find_address_with_context(
-            dht, 
-            key_id, 
-            &mut None, 
-            DhtSearchPolicy::FullSearch(Self::MAX_TASKS)
-        ).await
+    /// Value under caller-chosen DHT key, with default ttl
+    pub fn with_key(key: DhtKey) -> Self {
+        Self {
+            key,
+            ttl: Version::get() + DhtNode::TIMEOUT_VALUE,
+            value: Vec::new()
+        }
     }
 
-    /// Find address of node with given key ID 
-    pub async fn find_address_with_context(
-        dht: &Arc<Self>, 
-        key_id: &Arc<KeyId>,
-        ctx_opt: &mut Option<AddressSearchContext>,
-        policy: DhtSearchPolicy
-    ) -> Result<Option<(IpAddress, Arc<dyn KeyOption>)>> {
-        if ctx_opt.is_none() {
-            let key_id = Arc::new(hash(Self::dht_key_from_key_id(key_id, "address"))?);
-            ctx_opt.replace(
-                AddressSearchContext {
-                    iter: None,
-                    key_id
-                }
-            );
-        }
-        let Some(ctx) = ctx_opt else {
-            fail!("INTERNAL ERROR: cannot make address search context")
-        };
-        let mut addr_list = DhtNode::find_value(
-            dht,
-            &ctx.key_id,
-            |object| object.is::<AddressListBoxed>(),
-            &policy,
-            false, 
-            &mut ctx.iter
-        ).await?;
-        if let Some((key, addr_list)) = addr_list.pop() {
-            Ok(Some(Self::parse_value_as_address(key, addr_list)?))
-        } else {
-            Ok(None)
-        }
+    /// Set absolute expiration time 
+    pub fn with_ttl(mut self, ttl: i32) -> Self {
+        self.ttl = ttl;
+        self
     }
 
-    /// Get nodes of overlay with given ID
-    pub async fn find_overlay_nodes(
-        dht: &Arc<Self>, 
-        overlay_id: &Arc<OverlayShortId>,
-        iter: &mut Option<DhtIterator>
-    ) -> Result<Vec<(IpAddress, OverlayNode)>> {
-        DhtNode::find_overlay_nodes_with_context(
-            dht, 
-            overlay_id, 
-            &mut None,
-            DhtSearchPolicy::FullSearch(Self::MAX_TASKS), 
-            iter
-        ).await
+    /// Set value payload
+    pub fn with_value(mut self, value: Vec<u8>) -> Self {
+        self.value = value;
+        self
     }
 
-    /// Get nodes of overlay with given ID, keeping search context
-    pub async fn find_overlay_nodes_with_context(
+    /// Build unsigned OverlayNodes value for given overlay
+    pub fn build_overlay_nodes(self, overlay_id: &OverlayId) -> Result<DhtValue> {
+        self.check_ttl()?;
+        let overlay_id = Overlay {
+            name: overlay_id.to_vec().into()
+        };
+        let overlay_short_id = OverlayShortId::from_data(hash(overlay_id.clone())?);
+        let compressed = DhtNode::overlay_nodes_compression(&overlay_short_id, &self.key)
+            .map_err(|_| error!("DHT key does not match overlay for OverlayNodes value"))?;
+        DhtNode::deserialize_overlay_nodes(&self.value, compressed)?;
+        let value = DhtValue {
+            key: DhtKeyDescription {
+                id: overlay_id.into_boxed(),
+                key: self.key,
+                signature: Default::default(),
+                update_rule: UpdateRule::Dht_UpdateRule_OverlayNodes
+            },
+            ttl: self.ttl,
+            signature: Default::default(),
+            value: self.value.into()
+        };
+        Ok(value)
+    }
+
+    /// Build value with Signature rule, signed by key owning DHT key ID
+    pub fn build_signed(self, key: &Arc<dyn KeyOption>) -> Result<DhtValue> {
+        self.check_ttl()?;
+        if self.key.id.as_slice() != key.id().data() {
+            fail!("DHT key does not match signing key {}", key.id())
+        }
+        let key_description = DhtKeyDescription {
+            id: key.try_into()?,
+            key: self.key,
+            signature: Default::default(),
+            update_rule: UpdateRule::Dht_UpdateRule_Signature
+        };
+        let value = DhtValue {
+            key: key_description.sign(key)?,
+            ttl: self.ttl,
+            signature: Default::default(),
+            value: self.value.into()
+        };
+        value.sign(key)
+    }
+
+    fn check_ttl(&self) -> Result<()> {
+        if self.ttl <= Version::get() {
+            fail!("DHT value ttl {} is not in future", self.ttl)
+        }
+        Ok(())
+    }
+
+}
+
+/// Observer of consumed inbound queries: (source peer, query type tag)
+pub type DhtQueryObserver = Arc<dyn Fn(&Arc<KeyId>, u32) + Send + Sync>;
+
+/// Callback of own published record found absent in network: (key ID, record name)
+pub type DhtRecordObserver = Arc<dyn Fn(&Arc<KeyId>, &str) + Send + Sync>;
+
+/// Source of ping nonces, e.g. seeded generator for reproducible tests
+pub type DhtNonceSource = Arc<dyn Fn() -> i64 + Send + Sync>;
+
+/// Callback of overlay membership change: (added members, removed member key IDs)
+pub type DhtOverlayMembersObserver = 
+    Arc<dyn Fn(Vec<(IpAddress, OverlayNode)>, Vec<Arc<KeyId>>) + Send + Sync>;
+
+/// Durable backend for DHT peers and values, written through on table and storage updates
+pub trait DhtPersistence: Send + Sync {
+    fn save_node(&self, node: &Node) -> Result<()>;
+    fn load_nodes(&self) -> Result<Vec<Node>>;
+    fn save_value(&self, value: &DhtValue) -> Result<()>;
+    fn load_values(&self) -> Result<Vec<DhtValue>>;
+    /// Replace persisted records by given live ones, called after load
+    fn compact(&self, _nodes: &[Node], _values: &[DhtValue]) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Flat file persistence backend: append-only logs of length-prefixed boxed TL records.
+/// Records superseded by newer versions are filtered out on load by the usual 
+/// version and ttl rules, then logs are rewritten with live records only. 
+/// Corrupt records are skipped
+pub struct DhtFilePersistence {
+    lock: Mutex<()>,
+    nodes: PathBuf,
+    values: PathBuf
+}
+
+impl DhtFilePersistence {
+
+    /// Persistence in given directory
+    pub fn with_path(path: impl AsRef<Path>) -> Result<Self> {
+        std::fs::create_dir_all(path.as_ref())?;
+        let ret = Self {
+            lock: Mutex::new(()),
+            nodes: path.as_ref().join("nodes"),
+            values: path.as_ref().join("values")
+        };
+        Ok(ret)
+    }
+
+    fn append(&self, path: &Path, record: &[u8]) -> Result<()> {
+        let mut data = Vec::new();
+        Self::encode(&mut data, record);
+        let _lock = self.lock.lock().map_err(|_| error!("DHT persistence lock is poisoned"))?;
+        let mut file = std::fs::OpenOptions::new().create(true).append(true).open(path)?;
+        file.write_all(&data)?;
+        Ok(())
+    }
+
+    fn encode(data: &mut Vec<u8>, record: &[u8]) {
+        data.extend_from_slice(&(record.len() as u32).to_le_bytes());
+        data.extend_from_slice(record)
+    }
+
+    fn read(&self, path: &Path) -> Result<Vec<TLObject>> {
+        let data = {
+            let _lock = self.lock.lock().map_err(
+                |_| error!("DHT persistence lock is poisoned")
+            )?;
+            match std::fs::read(path) {
+                Ok(data) => data,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+                Err(e) => return Err(e.into())
+            }
+        };
+        let mut ret = Vec::new();
+        let mut pos = 0;
+        while pos + 4 <= data.len() {
+            let len = u32::from_le_bytes(data[pos..pos + 4].try_into()?) as usize;
+            pos += 4;
+            if pos + len > data.len() {
+                // Torn tail write
+                break
+            }
+            match deserialize_boxed(&data[pos..pos + len]) {
+                Ok(object) => ret.push(object),
+                Err(e) => log::warn!(
+                    target: TARGET_STORE, 
+                    "Skip corrupt DHT record in {}: {}", 
+                    path.display(), e
+                )
+            }
+            pos += len
+        }
+        Ok(ret)
+    }
+
+    // Replaced atomically, so failed rewrite leaves the old log intact
+    fn rewrite(&self, path: &Path, records: Vec<Vec<u8>>) -> Result<()> {
+        let mut data = Vec::new();
+        for record in records {
+            Self::encode(&mut data, &record)
+        }
+        let tmp = path.with_extension("tmp");
+        let _lock = self.lock.lock().map_err(|_| error!("DHT persistence lock is poisoned"))?;
+        std::fs::write(&tmp, &data)?;
+        std::fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+}
+
+impl DhtPersistence for DhtFilePersistence {
+
+    fn save_node(&self, node: &Node) -> Result<()> {
+        self.append(&self.nodes, &serialize_boxed(&node.clone().into_boxed())?)
+    }
+
+    fn load_nodes(&self) -> Result<Vec<Node>> {
+        let mut ret = Vec::new();
+        for object in self.read(&self.nodes)? {
+            match object.downcast::<NodeBoxed>() {
+                Ok(node) => ret.push(node.only()),
+                Err(object) => log::warn!(
+                    target: TARGET_TABLE, 
+                    "Skip wrong DHT node record: {:?}", 
+                    object
+                )
+            }
+        }
+        Ok(ret)
+    }
+
+    fn save_value(&self, value: &DhtValue) -> Result<()> {
+        self.append(&self.values, &serialize_boxed(&value.clone().into_boxed())?)
+    }
+
+    fn load_values(&self) -> Result<Vec<DhtValue>> {
+        let mut ret = Vec::new();
+        for object in self.read(&self.values)? {
+            match object.downcast::<DhtValueBoxed>() {
+                Ok(value) => ret.push(value.only()),
+                Err(object) => log::warn!(
+                    target: TARGET_STORE, 
+                    "Skip wrong DHT value record: {:?}", 
+                    object
+                )
+            }
+        }
+        Ok(ret)
+    }
+
+    fn compact(&self, nodes: &[Node], values: &[DhtValue]) -> Result<()> {
+        let nodes = nodes.iter().map(
+            |node| serialize_boxed(&node.clone().into_boxed())
+        ).collect::<Result<Vec<_>>>()?;
+        self.rewrite(&self.nodes, nodes)?;
+        let values = values.iter().map(
+            |value| serialize_boxed(&value.clone().into_boxed())
+        ).collect::<Result<Vec<_>>>()?;
+        self.rewrite(&self.values, values)
+    }
+
+}
+
+/// DHT node configuration
+#[derive(Clone)]
+pub struct DhtConfig {
+    /// Replace bucketed node by update of the same version when its address list differs.
+    /// Such updates are logged anyway, so operators can spot peers re-signing without bump
+    pub accept_same_version_updates: bool,
+    /// Period of FindNode lookups toward random keys of sparse buckets, None to disable.
+    /// Bucket is sparse when it holds less than target occupancy peers
+    pub bucket_refresh_interval: Option<Duration>,
+    pub bucket_target_occupancy: usize,
+    /// Register and update DHT metrics of this node, has effect with telemetry feature only
+    pub enable_telemetry: bool,
+    /// Bad score added to DHT peer per query: ADNL reports a missing answer as timeout, 
+    /// and refused or failed query as error, which is usually scored milder
+    pub query_error_penalty: u8,
+    pub query_timeout_penalty: u8,
+    /// Fail searches with DhtError::NoPeers when no DHT peers are known, 
+    /// instead of reporting nothing found
+    pub fail_on_no_peers: bool,
+    /// Key types (as in KeyOption::type_id) accepted for DHT peers and signed values, 
+    /// None to accept any
+    pub accepted_key_types: Option<HashSet<i32>>,
+    /// Order of known nodes handed out as routing hints in FindValue answers
+    pub known_nodes_order: DhtNodesOrder,
+    /// Most routing hints handed out per FindValue answer, whatever number query asks
+    pub max_routing_hints: usize,
+    /// Query types requiring known source, for nodes exposed to abuse
+    pub source_policy: DhtSourcePolicy,
+    /// Overlays whose node lists are hosted by this node, None or empty to host any
+    pub overlay_allowlist: Option<HashSet<Arc<OverlayShortId>>>,
+    /// Publish and search overlay nodes lists deflate-compressed, under DHT key name 
+    /// "nodes.deflate" instead of "nodes". Replicas host both kinds anyway, but nodes 
+    /// without compression support neither host nor find compressed lists, 
+    /// so it is to be turned on only when all overlay members run with it.
+    /// Lists of 16 nodes shrink by about 24%, of 256 nodes by about 28%, 
+    /// while single-node list grows by 5 bytes
+    pub compress_overlay_nodes: bool,
+    /// Order DHT peers of equal search rank by key ID, so search path is reproducible 
+    /// for given table
+    pub deterministic_order: bool,
+    /// Lower search rank of DHT peers by their share of failed queries
+    pub reliability_ordering: bool,
+    /// Good (not bad scored) known peers required, with own address stored, 
+    /// for node to report readiness
+    pub ready_min_peers: usize,
+    /// Store values to reliable DHT peers first, others are used if quorum is not met
+    pub reliable_store_first: bool,
+    /// Answer FindValue misses with empty node list: hides routing table from scanners, 
+    /// but makes the node useless as a router for others' searches
+    pub private_mode_no_routing_hints: bool,
+    /// Capacity of known peers cache, allocated at construction. 
+    /// Other DHT maps are lock-free hash tries growing node by node, 
+    /// so they have no capacity to preallocate
+    pub max_peers: u32,
+    /// Most peers held in buckets. In full table, new peer displaces farthest worst 
+    /// scored one only if it is closer or the one is scored bad
+    pub max_table_peers: u32,
+    /// Limits of auxiliary per-peer maps (bad peer scores, RTTs, query success rates, 
+    /// useful answer scores, gossip windows)
+    pub max_bad_peers: usize,
+    pub max_peer_rtts: usize,
+    pub max_peer_reliability: usize,
+    pub max_helpful_peers: usize,
+    pub max_gossip_sources: usize,
+    /// Separate budgets of concurrent outbound queries, so store bursts don't starve lookups
+    pub max_search_queries: usize,
+    pub max_store_queries: usize,
+    /// Concurrent member address resolutions shared by whole overlay nodes search 
+    /// (kept in its context across calls), None for no shared limit
+    pub max_overlay_resolves: Option<usize>,
+    /// Deepest allowed nesting of overlay searches chained by caller
+    pub max_overlay_depth: u32,
+    /// Ceiling of approximate memory held by tables, bytes, None for no ceiling.
+    /// When exceeded, soonest expiring values and worst scored peers are evicted, each 
+    /// in proportion to its share of usage, down to 7/8 of ceiling. Own values are kept
+    pub max_memory: Option<u64>,
+    /// Capacity of queue inbound Store values are verified from off the query path, 
+    /// None to verify them inline. Queueing needs Tokio runtime at construction, 
+    /// which fails otherwise
+    pub store_queue_capacity: Option<usize>,
+    /// Number of distinct replicas which must confirm stored value before store succeeds, 
+    /// at most 255
+    pub store_quorum: usize,
+    /// Backend peers and values are written through to, None to keep them in memory only
+    pub persistence: Option<Arc<dyn DhtPersistence>>,
+    /// Salt mixed into DHT key IDs to separate private network key space from public one
+    pub key_salt: Option<Vec<u8>>,
+    pub query_observer: Option<DhtQueryObserver>,
+    /// Ping nonce source, None for thread RNG
+    pub nonce_source: Option<DhtNonceSource>,
+    /// Peers accepted from nodes lists suggested by one source per gossip window, 
+    /// None for no limit. Excess nodes of a list are dropped, so one peer cannot 
+    /// churn buckets with a flood of crafted nodes
+    pub max_gossip_peers: Option<u32>,
+    pub gossip_window: Duration,
+    /// Delay after which a slow value query is duplicated to next best peer, None to disable.
+    /// Number of such extra queries per value search is capped
+    pub hedge_delay: Option<Duration>,
+    pub max_hedged_queries: u32,
+    /// Period of re-signing local node sent in query prefix, None to disable
+    pub local_node_refresh_interval: Option<Duration>,
+    /// Period of re-propagating held values to closest peers, None to disable
+    pub replica_repair_interval: Option<Duration>
+}
+
+impl Default for DhtConfig {
+    fn default() -> Self {
+        Self {
+            accept_same_version_updates: false,
+            bucket_refresh_interval: None,
+            bucket_target_occupancy: 10,
+            enable_telemetry: true,
+            query_error_penalty: 1,
+            query_timeout_penalty: 2,
+            fail_on_no_peers: false,
+            accepted_key_types: None,
+            known_nodes_order: DhtNodesOrder::FarFirst,
+            max_routing_hints: 16,
+            source_policy: DhtSourcePolicy::default(),
+            overlay_allowlist: None,
+            compress_overlay_nodes: false,
+            deterministic_order: false,
+            reliability_ordering: false,
+            ready_min_peers: 8,
+            reliable_store_first: false,
+            private_mode_no_routing_hints: false,
+            max_peers: DhtNode::MAX_PEERS,
+            max_table_peers: DhtNode::MAX_PEERS,
+            max_bad_peers: DhtNode::MAX_PEERS as usize,
+            max_peer_rtts: DhtNode::MAX_PEERS as usize,
+            max_peer_reliability: DhtNode::MAX_PEERS as usize,
+            max_helpful_peers: DhtNode::MAX_PEERS as usize,
+            max_gossip_sources: DhtNode::MAX_PEERS as usize,
+            max_search_queries: DhtNode::MAX_SEARCH_QUERIES,
+            max_store_queries: DhtNode::MAX_STORE_QUERIES,
+            max_overlay_resolves: None,
+            max_overlay_depth: 4,
+            max_memory: None,
+            store_queue_capacity: None,
+            store_quorum: 1,
+            persistence: None,
+            key_salt: None,
+            query_observer: None,
+            nonce_source: None,
+            max_gossip_peers: None,
+            gossip_window: Duration::from_secs(60),
+            hedge_delay: None,
+            max_hedged_queries: 2,
+            local_node_refresh_interval: None,
+            replica_repair_interval: None
+        }
+    }
+}
+
+/// Difference of value held locally and by DHT peer under given key: 
+/// ttl on each side (None if absent or expired) and whether payloads differ
+#[derive(Clone, Debug)]
+pub struct DhtKeyDiff {
+    pub key: DhtKeyId,
+    pub local_ttl: Option<i32>,
+    pub remote_ttl: Option<i32>,
+    pub value_differs: bool
+}
+
+impl DhtKeyDiff {
+    /// Check whether both sides hold the same value with the same ttl
+    pub fn is_consistent(&self) -> bool {
+        (self.local_ttl == self.remote_ttl) && !self.value_differs
+    }
+}
+
+/// Routing table churn counters since node start
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DhtChurnStats {
+    pub added: u64,
+    pub updated: u64,
+    pub evicted: u64,
+    pub demoted: u64
+}
+
+/// Counters of single maintenance pass
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DhtMaintenanceReport {
+    pub expired_values: usize,
+    pub pinged_peers: usize,
+    pub answered_peers: usize,
+    pub pruned_peers: usize,
+    pub republished_values: usize
+}
+
+/// Consolidated snapshot of DHT node state
+#[derive(Clone, Debug, Default)]
+pub struct DhtStats {
+    pub known_peers: u32,
+    pub max_peers: u32,
+    pub table_peers: u64,
+    pub buckets: Vec<(u8, usize)>,
+    pub bad_peers: u64,
+    pub values: u64,
+    pub value_bytes: u64,
+    /// Share of successful queries over tracked peers, None if nothing tracked yet
+    pub query_success_rate: Option<f32>
+}
+
+#[derive(Default)]
+struct DhtChurn {
+    added: AtomicU64,
+    updated: AtomicU64,
+    evicted: AtomicU64,
+    demoted: AtomicU64
+}
+
+#[cfg(feature = "telemetry")]
+struct DhtTelemetry {
+    bad_peers: Arc<Metric>,
+    disagreements: Arc<Metric>,
+    gossip: Arc<Metric>,
+    helpful: Arc<Metric>,
+    peers: Arc<Metric>,
+    peers_added: Arc<Metric>,
+    peers_demoted: Arc<Metric>,
+    peers_evicted: Arc<Metric>,
+    peers_updated: Arc<Metric>,
+    memory: Arc<Metric>,
+    reliability: Arc<Metric>,
+    rtts: Arc<Metric>,
+    store_queue: Arc<Metric>,
+    values: Arc<Metric>
+}
+
+struct DhtAlloc {
+    bad_peers: AtomicU64,
+    disagreements: AtomicU64,
+    gossip: AtomicU64,
+    helpful: AtomicU64,
+    peers: Arc<AtomicU64>,
+    reliability: AtomicU64,
+    rtts: AtomicU64,
+    store_queue: AtomicU64,
+    // Entries in buckets. Unlike peers counter, it is exact: superseded and removed 
+    // node objects are released lazily
+    table_peers: AtomicU64,
+    value_bytes: AtomicU64,
+    values: Arc<AtomicU64>
+}
+
+struct OverlayNodeResolveContext {
+    node: OverlayNode,
+    key: Arc<dyn KeyOption>,
+    search: Option<AddressSearchContext>
+}
+
+pub struct AddressSearchContext {
+    budget: Option<u32>,
+    iter: Option<DhtIterator>,
+    key_id: Arc<DhtKeyId>,
+}
+
+impl AddressSearchContext {
+    /// Rebuild search iterator from current DHT peers, also resuming finished search. 
+    /// Peers already tried may be queried again
+    pub fn rebuild_iterator(&mut self, dht: &DhtNode) {
+        self.iter.replace(DhtIterator::with_key_id(dht, self.key_id.clone()));
+    }
+}
+
+/// In-flight DHT value search
+pub struct SearchInfo {
+    pub key_id: DhtKeyId,
+    pub started_at: Instant,
+    pub policy: DhtSearchPolicy,
+    pub peers_queried: u64
+}
+
+struct SearchEntry {
+    key_id: DhtKeyId,
+    started_at: Instant,
+    policy: DhtSearchPolicy,
+    peers_queried: Arc<AtomicU64>
+}
+
+struct SearchGuard<'a> {
+    dht: &'a DhtNode,
+    id: u64
+}
+
+impl Drop for SearchGuard<'_> {
+    fn drop(&mut self) {
+        self.dht.searches.remove(&self.id);
+    }
+}
+
+/// Query outcomes of DHT peer
+#[derive(Default)]
+struct PeerStats {
+    failures: AtomicU64,
+    successes: AtomicU64
+}
+
+impl PeerStats {
+    fn reliability(&self) -> Option<f32> {
+        let successes = self.successes.load(Ordering::Relaxed);
+        let total = successes + self.failures.load(Ordering::Relaxed);
+        if total == 0 {
+            None
+        } else {
+            Some(successes as f32 / total as f32)
+        }
+    }
+}
+
+/// Entry of auxiliary per-peer map, stamped with last update time for trimming
+struct AuxEntry<V> {
+    seen: AtomicU64,
+    value: V
+}
+
+impl<V> AuxEntry<V> {
+    fn with_value(value: V) -> Self {
+        Self {
+            seen: AtomicU64::new(Version::get() as u64),
+            value
+        }
+    }
+
+    fn seen(&self) -> u64 {
+        self.seen.load(Ordering::Relaxed)
+    }
+
+    fn touch(&self) {
+        self.seen.store(Version::get() as u64, Ordering::Relaxed)
+    }
+}
+
+impl<V> std::ops::Deref for AuxEntry<V> {
+    type Target = V;
+    fn deref(&self) -> &V {
+        &self.value
+    }
+}
+
+/// Peers queried by value search in order, with peer returned accepted value
+#[derive(Clone, Debug, Default)]
+pub struct DhtLookupTrace {
+    pub queried: Vec<Arc<KeyId>>,
+    pub responder: Option<Arc<KeyId>>
+}
+
+/// Value returned by peer during value search
+struct FoundValue {
+    key: DhtKeyDescription,
+    object: TLObject,
+    peer: Arc<KeyId>,
+    stop: bool,
+    ttl: i32
+}
+
+/// Parameters of value search: policy, whether to collect values from all peers, 
+/// whether to verify freshness strictly, optional limit of queried peers, 
+/// and optional trace of queried peers
+struct SearchOptions<'a> {
+    all: bool,
+    budget: &'a mut Option<u32>,
+    policy: &'a DhtSearchPolicy,
+    strict: bool,
+    trace: Option<&'a mut DhtLookupTrace>
+}
+
+/// Spawned queries of a search, aborted when search is dropped
+struct SearchTasks(Vec<tokio::task::JoinHandle<()>>);
+
+impl Drop for SearchTasks {
+    fn drop(&mut self) {
+        for task in self.0.iter() {
+            task.abort()
+        }
+    }
+}
+
+/// FindValue query shared by all peer queries of a search, including hedged ones
+#[derive(Clone)]
+struct ValueQuery {
+    key_id: Arc<DhtKeyId>,
+    query: Arc<TaggedTlObject>,
+    strict: bool
+}
+
+pub struct OverlayNodesSearchContext {
+    depth: u32,
+    key_id: Arc<DhtKeyId>,
+    resolve_limiter: Option<Arc<Semaphore>>,
+    search: VecDeque<OverlayNodeResolveContext>,
+    stored: AddressCache
+}
+
+impl OverlayNodesSearchContext {
+
+    /// Nesting depth of overlay discovery this search belongs to, 0 for top level one
+    pub fn depth(&self) -> u32 {
+        self.depth
+    }
+
+    fn forget_stored(&mut self) {
+        self.stored = AddressCache::with_limit(DhtNode::MAX_PEERS)
+    }
+
+    fn with_params(dht: &DhtNode, overlay_id: &Arc<OverlayShortId>, depth: u32) -> Result<Self> {
+        let ret = Self {
+            depth,
+            key_id: Arc::new(dht.overlay_nodes_key_id(overlay_id)?),
+            resolve_limiter: dht.config.max_overlay_resolves.map(
+                |limit| Arc::new(Semaphore::new(limit))
+            ),
+            search: VecDeque::new(),
+            stored: AddressCache::with_limit(DhtNode::MAX_PEERS)
+        };
+        Ok(ret)
+    }
+
+}
+
+/// DHT Node
+pub struct DhtNode {
+    address_stored: AtomicBool,
+    adnl: Arc<AdnlNode>,
+    buckets: lockfree::map::Map<u8, lockfree::map::Map<Arc<KeyId>, NodeObject>>,
+    bad_peers: lockfree::map::Map<Arc<KeyId>, AuxEntry<AtomicU8>>,
+    churn: DhtChurn,
+    config: DhtConfig,
+    disagreements: lockfree::map::Map<Arc<KeyId>, AuxEntry<AtomicU64>>,
+    // Peers accepted from each source in current gossip window: window start << 32 | count
+    gossip: lockfree::map::Map<Arc<KeyId>, AuxEntry<AtomicU64>>,
+    // Decaying count of useful answers: update time in high 32 bits, score in low ones
+    helpful: lockfree::map::Map<Arc<KeyId>, AuxEntry<AtomicU64>>,
+    // Time of last memory trim which found nothing to evict, 0 if last one succeeded
+    memory_trim_failed: AtomicU64,
+    query_failures: AtomicU64,
+    known_peers: AddressCache,
+    node_key: Arc<dyn KeyOption>,
+    protected_peers: lockfree::map::Map<Arc<KeyId>, ()>,
+    // Keys of values published by this node, exempt from memory limit eviction
+    published: lockfree::map::Map<DhtKeyId, ()>,
+    query_prefix: RwLock<Arc<Vec<u8>>>,
+    reliability: lockfree::map::Map<Arc<KeyId>, AuxEntry<PeerStats>>,
+    rtts: lockfree::map::Map<Arc<KeyId>, AuxEntry<AtomicU64>>,
+    search_limiter: Semaphore,
+    search_seqno: AtomicU64,
+    searches: lockfree::map::Map<u64, SearchEntry>,
+    storage: lockfree::map::Map<DhtKeyId, ValueObject>,
+    store_limiter: Semaphore,
+    store_queue: Option<mpsc::Sender<DhtValue>>,
+    tag_dht_ping: u32,
+    tag_get_signed_address_list: u32,
+    tag_find_node: u32,
+    tag_find_value: u32,
+    tag_store: u32,
+    task_seqno: AtomicU64,
+    tasks: lockfree::map::Map<u64, (DhtTaskKind, tokio::task::AbortHandle)>,
+    #[cfg(feature = "telemetry")]
+    telemetry: Option<DhtTelemetry>,
+    allocated: DhtAlloc
+}
+
+impl DhtNode {
+
+    const AUX_TRIM_SHARE: usize = 8; // Aux maps are trimmed by 1/8 of their limit
+    const BITS: [u8; 16] = [
+        4, 3, 2, 2, 1, 1, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0
+    ];
+
+    const HELPFUL_HALF_LIFE: u64 = 600; // Seconds
+    const MAINTENANCE_PINGS: usize = 16;
+    const MAX_DISAGREEMENTS: u64 = 3;
+    const MAX_FAIL_COUNT: u8 = 5;
+    const MAX_FAIL_SCORE: u8 = Self::MAX_FAIL_COUNT + 2;
+    const MAX_HELPFUL_BONUS: u8 = 4;
+    const MAX_INFLATED_SIZE: u64 = 1 << 20; // Bytes
+    const MAX_PEERS: u32 = 65536;
+    const MAX_RELIABILITY_PENALTY: u8 = 8;
+    const MAX_RTT_PENALTY: u8 = 8;
+    const MAX_SEARCH_QUERIES: usize = 1024;
+    const MAX_STORE_QUERIES: usize = 256;
+    const MAX_TASKS: u8 = 5;
+    // Approximate memory footprints of table entries, bytes
+    const MEMORY_AUX_ENTRY: u64 = 64;
+    const MEMORY_PEER_ENTRY: u64 = 512;
+    const MEMORY_VALUE_ENTRY: u64 = 256;
+    const MEMORY_TRIM_RETRY: u64 = 10; // Seconds
+    const MEMORY_TRIM_SHARE: u64 = 8; // Memory is trimmed by 1/8 of ceiling below it
+    const MIN_ESTIMATE_PEERS: usize = 8;
+    const MIN_QUORUM: usize = 3;
+    const MIN_RELIABILITY: f32 = 0.9;
+    const MIN_RELIABILITY_SAMPLES: u64 = 4;
+    const MIN_TTL_LEFT: i32 = 60; // Seconds
+    const REFRESH_PEERS: usize = 3;
+    const REPAIR_PEERS: usize = 6;
+    const REPUBLISH_TTL_LEFT: i32 = Self::TIMEOUT_VALUE / 4; // Seconds
+    const ROUTING_TABLE_MAGIC: [u8; 4] = *b"DHTR";
+    const ROUTING_TABLE_VERSION: u32 = 1;
+    const RTT_PENALTY_STEP: u64 = 100000; // Microseconds per affinity bit
+    const SCAN_YIELD_STEP: usize = 1024; // Entries between yields in storage scans
+    const TIMEOUT_VALUE: i32 = 3600; // Seconds
+
+    /// Constructor 
+    pub fn with_adnl_node(adnl: Arc<AdnlNode>, key_tag: usize) -> Result<Arc<Self>> {
+        Self::with_adnl_node_and_config(adnl, key_tag, DhtConfig::default())
+    }
+
+    /// Constructor with explicit configuration
+    pub fn with_adnl_node_and_config(
+        adnl: Arc<AdnlNode>, 
+        key_tag: usize,
+        config: DhtConfig
+    ) -> Result<Arc<Self>> {
+        let node_key = adnl.key_by_tag(key_tag)?;
+        #[cfg(feature = "telemetry")]
+        let telemetry = config.enable_telemetry.then(
+            || DhtTelemetry {
+                bad_peers: adnl.add_metric("DHT bad peers map size"),
+                disagreements: adnl.add_metric("DHT value disagreements map size"),
+                gossip: adnl.add_metric("DHT gossip windows map size"),
+                helpful: adnl.add_metric("DHT helpful peers map size"),
+                peers: adnl.add_metric("Alloc DHT peers"),
+                peers_added: adnl.add_metric("DHT peers added"),
+                peers_demoted: adnl.add_metric("DHT peers demoted"),
+                peers_evicted: adnl.add_metric("DHT peers evicted"),
+                peers_updated: adnl.add_metric("DHT peers updated"),
+                memory: adnl.add_metric("DHT memory usage, bytes"),
+                reliability: adnl.add_metric("DHT peer reliability map size"),
+                rtts: adnl.add_metric("DHT peer RTTs map size"),
+                store_queue: adnl.add_metric("DHT store queue depth"),
+                values: adnl.add_metric("Alloc DHT values")
+            }
+        );
+        let allocated = DhtAlloc {
+            bad_peers: AtomicU64::new(0),
+            disagreements: AtomicU64::new(0),
+            gossip: AtomicU64::new(0),
+            helpful: AtomicU64::new(0),
+            peers: Arc::new(AtomicU64::new(0)),
+            reliability: AtomicU64::new(0),
+            rtts: AtomicU64::new(0),
+            store_queue: AtomicU64::new(0),
+            table_peers: AtomicU64::new(0),
+            value_bytes: AtomicU64::new(0),
+            values: Arc::new(AtomicU64::new(0))
+        };
+        if config.max_peers == 0 {
+            fail!("DHT known peers capacity must be positive")
+        }
+        if config.max_table_peers == 0 {
+            fail!("DHT peer table limit must be positive")
+        }
+        if (config.max_search_queries == 0) || (config.max_store_queries == 0) {
+            fail!("DHT query budgets must be positive")
+        }
+        if config.max_overlay_resolves == Some(0) {
+            fail!("DHT overlay resolve budget must be positive")
+        }
+        if config.store_quorum > u8::MAX as usize {
+            fail!("DHT store quorum must not exceed {}", u8::MAX)
+        }
+        let (store_queue, store_queue_reader) = match config.store_queue_capacity {
+            Some(0) => fail!("DHT store queue capacity must be positive"),
+            Some(capacity) => {
+                let (sender, reader) = mpsc::channel(capacity);
+                (Some(sender), Some(reader))
+            },
+            None => (None, None)
+        };
+        let known_peers = AddressCache::with_limit(config.max_peers);
+        let search_queries = config.max_search_queries;
+        let store_queries = config.max_store_queries;
+        let ret = Self {
+            address_stored: AtomicBool::new(false),
+            adnl,
+            buckets: lockfree::map::Map::new(),
+            bad_peers: lockfree::map::Map::new(), 
+            churn: DhtChurn::default(),
+            config,
+            disagreements: lockfree::map::Map::new(),
+            gossip: lockfree::map::Map::new(),
+            helpful: lockfree::map::Map::new(),
+            memory_trim_failed: AtomicU64::new(0),
+            query_failures: AtomicU64::new(0),
+            known_peers,
+            node_key,
+            protected_peers: lockfree::map::Map::new(),
+            published: lockfree::map::Map::new(),
+            query_prefix: RwLock::new(Arc::new(Vec::new())),
+            reliability: lockfree::map::Map::new(),
+            rtts: lockfree::map::Map::new(),
+            search_limiter: Semaphore::new(search_queries),
+            search_seqno: AtomicU64::new(0),
+            searches: lockfree::map::Map::new(),
+            storage: lockfree::map::Map::new(),
+            store_limiter: Semaphore::new(store_queries),
+            store_queue,
+            tag_dht_ping: tag_from_boxed_type::<DhtPing>(),
+            tag_find_node: tag_from_boxed_type::<FindNode>(),
+            tag_find_value: tag_from_boxed_type::<FindValue>(),
+            tag_get_signed_address_list: tag_from_boxed_type::<GetSignedAddressList>(),
+            tag_store: tag_from_boxed_type::<Store>(),
+            task_seqno: AtomicU64::new(0),
+            tasks: lockfree::map::Map::new(),
+            #[cfg(feature = "telemetry")]
+            telemetry,
+            allocated
+        };
+        ret.refresh_local_node()?;
+        let ret = Arc::new(ret);
+        if let Some(reader) = store_queue_reader {
+            Self::start_store_worker(&ret, reader)?
+        }
+        Ok(ret)
+    }
+
+    /// Re-verify all values in local storage, return entries failed verification. 
+    /// Non-empty result indicates corruption or a bug in store path
+    pub async fn audit_storage(&self) -> Vec<(DhtKeyId, Result<()>)> {
+        let mut ret = Vec::new();
+        for (i, key) in self.storage_keys().iter().enumerate() {
+            Self::yield_on_scan(i).await;
+            let Some(value) = self.storage.get(key) else {
+                continue
+            };
+            let result = self.verify_stored_value(key, &value.val().object);
+            if result.is_err() {
+                ret.push((*key, result))
+            }
+        }
+        ret
+    }
+
+    /// Put value into local storage via the same validation path as network Store, 
+    /// returns whether it was stored
+    pub fn accept_value(&self, value: DhtValue) -> Result<bool> {
+        self.insert_value(value, true)
+    }
+
+    /// Get in-flight DHT value searches
+    pub fn active_searches(&self) -> Vec<SearchInfo> {
+        self.searches.iter().map(
+            |search| {
+                let search = search.val();
+                SearchInfo {
+                    key_id: search.key_id,
+                    started_at: search.started_at,
+                    policy: search.policy.clone(),
+                    peers_queried: search.peers_queried.load(Ordering::Relaxed)
+                }
+            }
+        ).collect()
+    }
+
+    /// Add DHT peer 
+    pub fn add_peer(&self, peer: &Node) -> Result<Option<Arc<KeyId>>> {
+        self.insert_peer(peer, true)
+    }
+
+    /// Add DHT peer exempt from bad peer demotion
+    pub fn add_protected_peer(&self, peer: &Node) -> Result<Option<Arc<KeyId>>> {
+        let ret = self.add_peer(peer)?;
+        if let Some(peer) = &ret {
+            self.mark_protected(peer)?
+        }
+        Ok(ret)
+    }
+
+    /// Get number of DHT peers per affinity bucket (non-empty buckets only)
+    pub fn bucket_occupancy(&self) -> Vec<(u8, usize)> {
+        let mut ret = Vec::new();
+        for affinity in 0..=255 {
+            if let Some(bucket) = self.buckets.get(&affinity) {
+                let count = bucket.val().iter().count();
+                if count > 0 {
+                    ret.push((affinity, count))
+                }
+            }
+        }
+        ret
+    }
+
+    /// Get routing table churn counters
+    pub fn churn_stats(&self) -> DhtChurnStats {
+        DhtChurnStats {
+            added: self.churn.added.load(Ordering::Relaxed),
+            updated: self.churn.updated.load(Ordering::Relaxed),
+            evicted: self.churn.evicted.load(Ordering::Relaxed),
+            demoted: self.churn.demoted.load(Ordering::Relaxed)
+        }
+    }
+
+    /// Compare values held locally under given DHT key IDs with those given peer serves, 
+    /// tombstones included. Peer is probed by FindValue per key, so routing hints it 
+    /// answers with are learned. Keys whose comparison failed get error of their own
+    pub async fn compare_storage_with(
+        &self, 
+        peer: &Arc<KeyId>, 
+        keys: &[DhtKeyId]
+    ) -> Vec<(DhtKeyId, Result<DhtKeyDiff>)> {
+        let mut ret = Vec::with_capacity(keys.len());
+        for key in keys {
+            let diff = self.compare_value_with(peer, key).await;
+            ret.push((*key, diff))
+        }
+        ret
+    }
+
+    /// Estimate DHT network size from density of closest buckets. Peers sharing 
+    /// at least N leading bits with us cover 1/2^N of key space, and closest buckets 
+    /// are the most complete ones. None if there are too few peers to estimate
+    pub fn estimate_network_size(&self) -> Option<u64> {
+        let mut count = 0;
+        for (affinity, occupancy) in self.bucket_occupancy().into_iter().rev() {
+            count += occupancy;
+            if count >= Self::MIN_ESTIMATE_PEERS {
+                let size = count as f64 * 2f64.powi(affinity as i32);
+                return Some(size.min(u64::MAX as f64) as u64)
+            }
+        }
+        None
+    }
+
+    /// Export signed local node as base64 of its boxed TL form, to share it as bootstrap
+    pub fn export_node(&self) -> Result<String> {
+        let node = self.sign_local_node()?;
+        Ok(base64_encode(&serialize_boxed(&node.into_boxed())?))
+    }
+
+    /// Find DHT nodes
+    pub async fn find_dht_nodes(&self, dst: &Arc<KeyId>) -> Result<bool> {
+        Ok(self.query_dht_nodes(dst).await?.is_some())
+    }
+
+    /// Find DHT nodes, returning the nodes list as received from peer
+    pub async fn find_dht_nodes_list(&self, dst: &Arc<KeyId>) -> Result<Vec<Node>> {
+        Ok(self.query_dht_nodes(dst).await?.unwrap_or_default())
+    }
+
+    /// Find DHT nodes close to given key, returning the nodes list as received from peer
+    pub async fn find_dht_nodes_toward(
+        &self, 
+        dst: &Arc<KeyId>, 
+        key: &DhtKeyId
+    ) -> Result<Vec<Node>> {
+        Ok(self.query_dht_nodes_toward(dst, key).await?.unwrap_or_default())
+    }
+
+    /// Fetch address of node (locally) with given key ID 
+    pub async fn fetch_address(
+        &self,
+        key_id: &Arc<KeyId>
+    ) -> Result<Option<(IpAddress, Arc<dyn KeyOption>)>> {
+        let key = Self::dht_key_from_key_id(key_id, "address");
+        let value = self.search_dht_key(&self.value_key_id(&key)?);
+        if let Some(value) = value {
+            if value.value.is_empty() {
+                // Tombstone
+                return Ok(None)
+            }
+            let object = deserialize_boxed(&value.value)?;
+            Ok(Some(Self::parse_value_as_address(value.key, object)?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Find values published under given key ID and name for all idx in 0..=max_idx.
+    /// Assumes contiguous idx range; indices are searched concurrently
+    pub async fn fetch_all_idx(
+        dht: &Arc<Self>, 
+        key_id: &Arc<KeyId>, 
+        name: &str, 
+        max_idx: i32
+    ) -> Result<Vec<(i32, DhtKeyDescription, TLObject)>> {
+        let mut tasks = Vec::new();
+        for idx in 0..=max_idx {
+            let mut key = Self::dht_key_from_key_id(key_id, name);
+            key.idx = idx;
+            let key_id = Arc::new(dht.value_key_id(&key)?);
+            let dht = dht.clone();
+            let task = tokio::spawn(
+                async move {
+                    let options = SearchOptions {
+                        all: false,
+                        budget: &mut None,
+                        policy: &DhtSearchPolicy::FullSearch(DhtNode::MAX_TASKS),
+                        strict: true,
+                        trace: None
+                    };
+                    DhtNode::find_value(&dht, &key_id, |_| true, options, &mut None).await
+                }
+            );
+            tasks.push((idx, task))
+        }
+        let mut ret = Vec::new();
+        for (idx, task) in tasks {
+            if let Some((key, object)) = task.await??.pop() {
+                ret.push((idx, key, object))
+            }
+        }
+        Ok(ret)
+    }
+
+    /// Find address of node with given key ID 
+    pub async fn find_address(
+        dht: &Arc<Self>, 
+        key_id: &Arc<KeyId>
+    ) -> Result<Option<(IpAddress, Arc<dyn KeyOption>)>> {
+        DhtNode::find_address_with_context(
+            dht, 
+            key_id, 
+            &mut None, 
+            DhtSearchPolicy::FullSearch(Self::MAX_TASKS)
+        ).await
+    }
+
+    /// Find address of node with given key ID 
+    pub async fn find_address_with_context(
+        dht: &Arc<Self>, 
+        key_id: &Arc<KeyId>,
+        ctx_opt: &mut Option<AddressSearchContext>,
+        policy: DhtSearchPolicy
+    ) -> Result<Option<(IpAddress, Arc<dyn KeyOption>)>> {
+        let ret = DhtNode::find_address_impl(
+            dht, key_id, ctx_opt, policy, false, false, None
+        ).await?;
+        Ok(ret.map(|addr| (addr.ip, addr.key)))
+    }
+
+    /// Find address of node with given key ID, with derived key data and record ttl
+    pub async fn find_address_detailed(
+        dht: &Arc<Self>, 
+        key_id: &Arc<KeyId>,
+        ctx_opt: &mut Option<AddressSearchContext>,
+        policy: DhtSearchPolicy
+    ) -> Result<Option<ResolvedAddress>> {
+        DhtNode::find_address_impl(dht, key_id, ctx_opt, policy, false, false, None).await
+    }
+
+    /// Find address of node with given key ID trying FastSearch first and, on a miss, 
+    /// FullSearch with fresh context. Returns found address with the policy that found it
+    pub async fn find_address_fast_then_full(
+        dht: &Arc<Self>, 
+        key_id: &Arc<KeyId>
+    ) -> Result<Option<(IpAddress, Arc<dyn KeyOption>, DhtSearchPolicy)>> {
+        for policy in [
+            DhtSearchPolicy::FastSearch(1), 
+            DhtSearchPolicy::FullSearch(Self::MAX_TASKS)
+        ] {
+            let found = DhtNode::find_address_with_context(
+                dht, 
+                key_id, 
+                &mut None, 
+                policy.clone()
+            ).await?;
+            if let Some((ip, key)) = found {
+                return Ok(Some((ip, key, policy)))
+            }
+            log::debug!(target: TARGET_SEARCH, "Address of {} not found, widen search", key_id);
+        }
+        Ok(None)
+    }
+
+    /// Find address of node with given key ID, sending at most query_budget FindValue 
+    /// queries over the whole search (budget is kept in search context when resumed).
+    /// Returns found address, if any, and whether the budget is exhausted
+    pub async fn find_address_with_budget(
+        dht: &Arc<Self>, 
+        key_id: &Arc<KeyId>,
+        ctx_opt: &mut Option<AddressSearchContext>,
+        policy: DhtSearchPolicy,
+        query_budget: u32
+    ) -> Result<(Option<(IpAddress, Arc<dyn KeyOption>)>, bool)> {
+        let ret = DhtNode::find_address_impl(
+            dht, 
+            key_id, 
+            ctx_opt, 
+            policy, 
+            false, 
+            false, 
+            Some(query_budget)
+        ).await?;
+        let exhausted = matches!(ctx_opt, Some(AddressSearchContext { budget: Some(0), .. }));
+        Ok((ret.map(|addr| (addr.ip, addr.key)), exhausted))
+    }
+
+    /// Find address of node with given key ID, returning only address answering ping. 
+    /// Unreachable addresses are skipped and search continues with other replicas
+    pub async fn find_reachable_address_with_context(
+        dht: &Arc<Self>, 
+        key_id: &Arc<KeyId>,
+        ctx_opt: &mut Option<AddressSearchContext>,
+        policy: DhtSearchPolicy
+    ) -> Result<Option<(IpAddress, Arc<dyn KeyOption>)>> {
+        let ret = DhtNode::find_address_impl(
+            dht, key_id, ctx_opt, policy, false, true, None
+        ).await?;
+        Ok(ret.map(|addr| (addr.ip, addr.key)))
+    }
+
+    /// Find address of node with given key ID, accepting only verified values
+    /// which are not close to expiration. May cost extra queries when the first 
+    /// replicas return stale or improperly signed values
+    pub async fn find_verified_address_with_context(
+        dht: &Arc<Self>, 
+        key_id: &Arc<KeyId>,
+        ctx_opt: &mut Option<AddressSearchContext>,
+        policy: DhtSearchPolicy
+    ) -> Result<Option<(IpAddress, Arc<dyn KeyOption>)>> {
+        let ret = DhtNode::find_address_impl(
+            dht, key_id, ctx_opt, policy, true, false, None
+        ).await?;
+        Ok(ret.map(|addr| (addr.ip, addr.key)))
+    }
+
+    /// Get nodes of overlay with given ID
+    pub async fn find_overlay_nodes(
+        dht: &Arc<Self>, 
+        overlay_id: &Arc<OverlayShortId>,
+        iter: &mut Option<DhtIterator>
+    ) -> Result<Vec<(IpAddress, OverlayNode)>> {
+        DhtNode::find_overlay_nodes_with_context(
+            dht, 
+            overlay_id, 
+            &mut None,
+            DhtSearchPolicy::FullSearch(Self::MAX_TASKS), 
+            iter
+        ).await
+    }
+
+    /// Get nodes of overlay with given ID, keeping search context. Unlike other searches,
+    /// it does not stop on first resolved batch but pulls iterator rounds until it is over
+    pub async fn find_overlay_nodes_exhaustive(
+        dht: &Arc<Self>,
+        overlay_id: &Arc<OverlayShortId>,
+        ctx_search_opt: &mut Option<OverlayNodesSearchContext>,
+        policy: DhtSearchPolicy,
+        iter: &mut Option<DhtIterator>
+    ) -> Result<Vec<(IpAddress, OverlayNode)>> {
+        DhtNode::find_overlay_nodes_impl(
+            dht,
+            overlay_id,
+            ctx_search_opt,
+            policy,
+            usize::MAX,
+            None,
+            iter
+        ).await
+    }
+
+    /// Get nodes of overlay with given ID, keeping search context
+    pub async fn find_overlay_nodes_with_context(
+        dht: &Arc<Self>, 
+        overlay_id: &Arc<OverlayShortId>,
+        ctx_search_opt: &mut Option<OverlayNodesSearchContext>,
+        policy: DhtSearchPolicy,
+        iter: &mut Option<DhtIterator>
+    ) -> Result<Vec<(IpAddress, OverlayNode)>> {
+        DhtNode::find_overlay_nodes_impl(
+            dht, 
+            overlay_id, 
+            ctx_search_opt, 
+            policy, 
+            1, 
+            None, 
+            iter
+        ).await
+    }
+
+    /// Get nodes of overlay discovered while resolving another overlay, keeping search 
+    /// context. The crate itself never nests overlay searches: callers chaining them 
+    /// pass parent context, and nesting deeper than configured limit fails
+    pub async fn find_overlay_nodes_nested(
+        dht: &Arc<Self>, 
+        overlay_id: &Arc<OverlayShortId>,
+        parent: &OverlayNodesSearchContext,
+        ctx_search_opt: &mut Option<OverlayNodesSearchContext>,
+        policy: DhtSearchPolicy,
+        iter: &mut Option<DhtIterator>
+    ) -> Result<Vec<(IpAddress, OverlayNode)>> {
+        let depth = parent.depth + 1;
+        if depth > dht.config.max_overlay_depth {
+            fail!(
+                "Overlay {} discovery depth {} exceeds limit {}", 
+                overlay_id, depth, dht.config.max_overlay_depth
+            )
+        }
+        if ctx_search_opt.is_none() {
+            ctx_search_opt.replace(OverlayNodesSearchContext::with_params(dht, overlay_id, depth)?);
+        }
+        DhtNode::find_overlay_nodes_impl(
+            dht, 
+            overlay_id, 
+            ctx_search_opt, 
+            policy, 
+            1, 
+            None, 
+            iter
+        ).await
+    }
+
+    /// Get nodes of overlay with given ID, keeping search context, attempting to resolve 
+    /// at most max_resolve members per call. Unattempted members stay in context
+    pub async fn find_overlay_nodes_with_resolve_limit(
+        dht: &Arc<Self>, 
+        overlay_id: &Arc<OverlayShortId>,
+        ctx_search_opt: &mut Option<OverlayNodesSearchContext>,
+        policy: DhtSearchPolicy,
+        max_resolve: usize,
+        iter: &mut Option<DhtIterator>
+    ) -> Result<Vec<(IpAddress, OverlayNode)>> {
+        DhtNode::find_overlay_nodes_impl(
+            dht, 
+            overlay_id, 
+            ctx_search_opt, 
+            policy, 
+            1, 
+            Some(max_resolve), 
+            iter
+        ).await
+    }
+
+    /// Get nodes of overlay with given ID, keeping search context, until at least 
+    /// target_count distinct members are resolved or search is over
+    pub async fn find_overlay_nodes_with_target(
+        dht: &Arc<Self>, 
+        overlay_id: &Arc<OverlayShortId>,
+        ctx_search_opt: &mut Option<OverlayNodesSearchContext>,
+        policy: DhtSearchPolicy,
+        target_count: usize,
+        iter: &mut Option<DhtIterator>
+    ) -> Result<Vec<(IpAddress, OverlayNode)>> {
+        DhtNode::find_overlay_nodes_impl(
+            dht, 
+            overlay_id, 
+            ctx_search_opt, 
+            policy, 
+            target_count, 
+            None, 
+            iter
+        ).await
+    }
+
+    /// Find value published under given key ID and name, recording peers queried 
+    /// on the way and the one which returned the value
+    pub async fn find_value_traced(
+        dht: &Arc<Self>, 
+        key_id: &Arc<KeyId>, 
+        name: &str,
+        policy: &DhtSearchPolicy
+    ) -> Result<(Option<(DhtKeyDescription, TLObject)>, DhtLookupTrace)> {
+        let key_id = Arc::new(dht.value_key_id(&Self::dht_key_from_key_id(key_id, name))?);
+        let mut trace = DhtLookupTrace::default();
+        let options = SearchOptions {
+            all: false,
+            budget: &mut None,
+            policy,
+            strict: true,
+            trace: Some(&mut trace)
+        };
+        let mut found = DhtNode::find_value_with_peers(
+            dht, &key_id, |_| true, options, &mut None
+        ).await?;
+        Ok((found.pop().map(|found| (found.key, found.object)), trace))
+    }
+
+    /// Find values published under given key ID and name querying given peers only, 
+    /// in given order. Table peers suggested by answers are not queried 
+    pub async fn find_value_over<R: Into<DhtValueCheck>>(
+        dht: &Arc<Self>, 
+        key_id: &Arc<KeyId>, 
+        name: &str,
+        peers: Vec<Arc<KeyId>>,
+        check: impl Fn(&TLObject) -> R + Copy + Send + 'static,
+        all: bool
+    ) -> Result<Vec<(DhtKeyDescription, TLObject)>> {
+        let key_id = Arc::new(dht.value_key_id(&Self::dht_key_from_key_id(key_id, name))?);
+        let mut iter = Some(DhtIterator::with_peers(dht, key_id.clone(), peers));
+        let options = SearchOptions {
+            all,
+            budget: &mut None,
+            policy: &DhtSearchPolicy::FullSearch(Self::MAX_TASKS),
+            strict: true,
+            trace: None
+        };
+        DhtNode::find_value(dht, &key_id, check, options, &mut iter).await
+    }
+
+    /// Find values published under given key ID and name, with caller decision 
+    /// on each found value
+    pub async fn find_values_with_check(
+        dht: &Arc<Self>, 
+        key_id: &Arc<KeyId>, 
+        name: &str,
+        check: impl Fn(&TLObject) -> DhtValueCheck + Copy + Send + 'static,
+        policy: &DhtSearchPolicy
+    ) -> Result<Vec<(DhtKeyDescription, TLObject)>> {
+        let key_id = Arc::new(dht.value_key_id(&Self::dht_key_from_key_id(key_id, name))?);
+        let options = SearchOptions {
+            all: true,
+            budget: &mut None,
+            policy,
+            strict: true,
+            trace: None
+        };
+        DhtNode::find_value(dht, &key_id, check, options, &mut None).await
+    }
+
+    /// Get DHT peer via iterator
+    pub fn get_known_peer(&self, iter: &mut Option<AddressCacheIterator>) -> Option<Arc<KeyId>> {
+        loop {
+            let ret = if let Some(iter) = iter {
+                self.known_peers.next(iter)
+            } else {
+                let (new_iter, first) = self.known_peers.first();
+                iter.replace(new_iter);
+                first
+            };
+            if let Some(peer) = &ret {
+                if self.is_own_key(peer) {
+                    continue
+                }
+                if let Some(count) = self.bad_peers.get(peer) {
+                    if count.val().load(Ordering::Relaxed) >= Self::MAX_FAIL_COUNT {
+                        continue
+                    }
+                }
+                if !self.is_peer_in_table(peer) {
+                    // Evicted from table
+                    continue
+                }
+            }
+            break ret
+        }
+    }
+
+    /// Get known DHT nodes
+    pub fn get_known_nodes(&self, limit: usize) -> Result<Vec<Node>> {
+        self.get_known_nodes_ordered(limit, DhtNodesOrder::FarFirst)
+    }
+
+    /// Get known DHT nodes picked from buckets in given order
+    pub fn get_known_nodes_ordered(
+        &self, 
+        limit: usize, 
+        order: DhtNodesOrder
+    ) -> Result<Vec<Node>> {
+        if limit == 0 {
+            fail!("It is useless to ask for zero known nodes")
+        }
+        let mut ret = Vec::new();
+        let mut affinities: Vec<u8> = (0..=255).collect();
+        if order == DhtNodesOrder::CloseFirst {
+            affinities.reverse()
+        }
+        if order == DhtNodesOrder::Spread {
+            // Round-robin over live bucket iterators, so only returned nodes are copied
+            let guards: Vec<_> = affinities.into_iter().filter_map(
+                |i| self.buckets.get(&i)
+            ).collect();
+            let mut buckets: Vec<_> = guards.iter().map(|bucket| bucket.val().iter()).collect();
+            loop {
+                let mut picked = false;
+                for bucket in buckets.iter_mut() {
+                    if let Some(node) = bucket.next() {
+                        ret.push(node.val().object.clone());
+                        if ret.len() == limit {
+                            return Ok(ret)
+                        }
+                        picked = true
+                    }
+                }
+                if !picked {
+                    return Ok(ret)
+                }
+            }
+        }
+        for i in affinities {
+            if let Some(bucket) = self.buckets.get(&i) {
+                for node in bucket.val().iter() {         
+                    ret.push(node.val().object.clone());
+                    if ret.len() == limit {
+                        return Ok(ret)
+                    }
+                }
+            }
+        }
+        Ok(ret)
+    }
+                    
+    /// Get signed address list 
+    pub async fn get_signed_address_list(&self, dst: &Arc<KeyId>) -> Result<bool> {
+        Ok(self.query_signed_node(dst, None).await?.is_some())
+    }
+
+    /// Get signed node
+    pub fn get_signed_node(&self) -> Result<Node> {
+        self.sign_local_node()
+    }
+
+    /// Get signed node as advertised by DHT peer itself, with bounded wait. 
+    /// None if no answer or node is not signed by that peer
+    pub async fn get_signed_node_from(
+        &self, 
+        dst: &Arc<KeyId>, 
+        timeout: Duration
+    ) -> Result<Option<Node>> {
+        self.query_signed_node(dst, Some(timeout)).await
+    }
+
+    /// Parse node exported by export_node. Signature is checked when node is added as peer
+    pub fn import_node(s: &str) -> Result<Node> {
+        let object = deserialize_boxed(&base64_decode(s.trim())?)?;
+        let node = object.downcast::<NodeBoxed>().map_err(
+            |object| error!("Wrong exported DHT node: {:?}", object)
+        )?;
+        Ok(node.only())
+    }
+
+    /// Import known peers of another DHT node, returns number of accepted ones. 
+    /// Failing peers are skipped
+    pub fn import_peers_from(&self, other: &DhtNode) -> Result<usize> {
+        let mut ret = 0;
+        for node in other.get_known_nodes(other.config.max_peers as usize)? {
+            match self.add_peer(&node) {
+                Ok(Some(_)) => ret += 1,
+                Ok(None) => (),
+                Err(e) => log::debug!(
+                    target: TARGET_TABLE, 
+                    "Cannot import DHT peer {:?}: {}", 
+                    node, e
+                )
+            }
+        }
+        Ok(ret)
+    }
+
+    /// Re-verify nodes of given affinity bucket, returning parsed address of each
+    /// or the reason it is not valid anymore
+    pub fn inspect_bucket(&self, affinity: u8) -> Vec<(Arc<KeyId>, Result<IpAddress>)> {
+        let Some(bucket) = self.buckets.get(&affinity) else {
+            return Vec::new()
+        };
+        bucket.val().iter().map(
+            |node| {
+                let node_object = &node.val().object;
+                let addr = self.verify_other_node(node_object).and_then(
+                    |_| AdnlNode::parse_address_list(&node_object.addr_list)?.ok_or_else(
+                        || error!("Wrong DHT peer address {:?}", node_object.addr_list)
+                    )
+                );
+                (node.key().clone(), addr)
+            }
+        ).collect()
+    }
+
+    /// Node IP address
+    pub fn ip_address(&self) -> &IpAddress {
+        self.adnl.ip_address()
+    }
+
+    /// Node key
+    pub fn key(&self) -> &Arc<dyn KeyOption> {
+        &self.node_key
+    }
+
+    /// Check node is usable: own address was stored at least once and enough good 
+    /// DHT peers are known. Meant for readiness probes
+    pub fn is_ready(&self) -> bool {
+        if !self.address_stored.load(Ordering::Relaxed) {
+            return false
+        }
+        let mut good = 0;
+        let mut iter = None;
+        while good < self.config.ready_min_peers {
+            if self.get_known_peer(&mut iter).is_none() {
+                return false
+            }
+            good += 1
+        }
+        true
+    }
+
+    /// Check whether we are among k closest known nodes to given DHT key
+    pub fn is_responsible_for(&self, key: &DhtKeyId, k: usize) -> bool {
+        let own = Self::get_affinity(self.node_key.id().data(), key);
+        let mut closer = 0;
+        for bucket in self.buckets.iter() {
+            for node in bucket.val().iter() {
+                if Self::get_affinity(node.key().data(), key) > own {
+                    closer += 1;
+                    if closer >= k {
+                        return false
+                    }
+                }
+            }
+        }
+        closer < k
+    }
+
+    /// Run single maintenance pass: drop expired values, ping a batch of suspicious 
+    /// bucket entries, evict peers scored bad beyond threshold, and republish values 
+    /// signed by own node key which are close to expiration
+    pub async fn maintain_once(dht: &Arc<Self>) -> Result<DhtMaintenanceReport> {
+        let mut ret = DhtMaintenanceReport {
+            expired_values: dht.storage_gc().await,
+            ..Default::default()
+        };
+        dht.expire_gossip();
+        let mut suspicious = Vec::new();
+        let mut bad = Vec::new();
+        for bucket in dht.buckets.iter() {
+            for node in bucket.val().iter() {
+                if dht.protected_peers.get(node.key()).is_some() {
+                    continue
+                }
+                let Some(score) = dht.bad_peers.get(node.key()) else {
+                    continue
+                };
+                let score = score.val().load(Ordering::Relaxed);
+                if score >= Self::MAX_FAIL_COUNT {
+                    bad.push((*bucket.key(), node.key().clone()))
+                } else if (score > 0) && (suspicious.len() < Self::MAINTENANCE_PINGS) {
+                    suspicious.push(node.key().clone())
+                }
+            }
+        }
+        let (wait, mut queue_reader) = Wait::new();
+        for peer in suspicious {
+            let dht = dht.clone();
+            let wait = wait.clone();
+            wait.request();
+            tokio::spawn(
+                async move {
+                    match dht.ping(&peer).await {
+                        Ok(true) => wait.respond(Some(())),
+                        Ok(false) => wait.respond(None),
+                        Err(e) => {
+                            log::debug!(target: TARGET_TABLE, "Maintenance ping error: {}", e);
+                            wait.respond(None)
+                        }
+                    }
+                }
+            );
+            ret.pinged_peers += 1
+        }
+        while let Some(answered) = wait.wait(&mut queue_reader, false).await {
+            if answered.is_some() {
+                ret.answered_peers += 1
+            }
+        }
+        for (affinity, peer) in bad {
+            dht.evict_peer(affinity, &peer);
+            ret.pruned_peers += 1
+        }
+        let version = Version::get();
+        let own: Vec<DhtValue> = dht.storage.iter().filter_map(
+            |value| {
+                let object = &value.val().object;
+                let republish = (object.ttl > version) && 
+                    (object.ttl <= version + Self::REPUBLISH_TTL_LEFT) &&
+                    matches!(object.key.update_rule, UpdateRule::Dht_UpdateRule_Signature) &&
+                    (object.key.key.id.as_slice() == dht.node_key.id().data());
+                republish.then(|| object.clone())
+            }
+        ).collect();
+        for value in own {
+            match Self::store_signed(
+                dht, 
+                value.key.key, 
+                value.value.to_vec(), 
+                Version::get() + Self::TIMEOUT_VALUE, 
+                &dht.node_key
+            ).await {
+                Ok(_) => ret.republished_values += 1,
+                Err(e) => log::warn!(target: TARGET_STORE, "Cannot republish own value: {}", e)
+            }
+        }
+        log::debug!(target: TARGET_TABLE, "DHT maintenance pass: {:?}", ret);
+        Ok(ret)
+    }
+
+    /// Exempt DHT peer from bad peer demotion and eviction
+    pub fn mark_protected(&self, peer: &Arc<KeyId>) -> Result<()> {
+        add_unbound_object_to_map(&self.protected_peers, peer.clone(), || Ok(()))?;
+        if let Some(count) = self.bad_peers.get(peer) {
+            count.val().fetch_min(Self::MAX_FAIL_COUNT - 1, Ordering::Relaxed);
+        }
+        Ok(())
+    }
+
+    /// Load DHT peers and values from persistence backend, returns numbers of accepted ones
+    pub fn load_persisted(&self) -> Result<(usize, usize)> {
+        let Some(persistence) = &self.config.persistence else {
+            return Ok((0, 0))
+        };
+        let mut nodes = 0;
+        for node in persistence.load_nodes()? {
+            if self.insert_peer(&node, false)?.is_some() {
+                nodes += 1
+            }
+        }
+        let mut values = 0;
+        for value in persistence.load_values()? {
+            match self.insert_value(value, false) {
+                Ok(true) => values += 1,
+                Ok(false) => (),
+                Err(e) => log::debug!(target: TARGET_STORE, "Skip persisted DHT value: {}", e)
+            }
+        }
+        // Superseded and expired records are dropped from backend. Own values are not
+        // persisted, so they are left out as well
+        let live_nodes = self.get_known_nodes(self.config.max_peers as usize)?;
+        let version = Version::get();
+        let live_values: Vec<_> = self.storage.iter().filter_map(
+            |value| {
+                let ret = &value.val().object;
+                if (ret.ttl <= version) || self.published.get(value.key()).is_some() {
+                    None
+                } else {
+                    Some(ret.clone())
+                }
+            }
+        ).collect();
+        if let Err(e) = persistence.compact(&live_nodes, &live_values) {
+            log::warn!(target: TARGET_STORE, "Cannot compact persisted DHT records: {}", e)
+        }
+        Ok((nodes, values))
+    }
+
+    /// Load DHT peers from routing table file
+    pub fn load_routing_table(&self, path: impl AsRef<Path>) -> Result<usize> {
+        let data = std::fs::read(path)?;
+        let header_len = Self::ROUTING_TABLE_MAGIC.len() + 4;
+        if (data.len() < header_len) || !data.starts_with(&Self::ROUTING_TABLE_MAGIC) {
+            fail!("Wrong DHT routing table file format")
+        }
+        let version = u32::from_le_bytes(data[header_len - 4..header_len].try_into()?);
+        if version != Self::ROUTING_TABLE_VERSION {
+            fail!("Unsupported DHT routing table format version {}", version)
+        }
+        let nodes = deserialize_boxed(&data[header_len..])?
+            .downcast::<NodesBoxed>()
+            .map_err(|object| error!("Wrong DHT routing table contents: {:?}", object))?;
+        let mut ret = 0;
+        for node in nodes.only().nodes.iter() {
+            if self.add_peer(node)?.is_some() {
+                ret += 1
+            }
+        }
+        Ok(ret)
+    }
+
+    /// Measure round-trip time to DHT peer
+    pub async fn measure_peer(&self, peer: &Arc<KeyId>) -> Result<Option<Duration>> {
+        let start = Instant::now();
+        if !self.ping(peer).await? {
+            return Ok(None)
+        }
+        let rtt = start.elapsed();
+        self.update_peer_rtt(peer, rtt)?;
+        Ok(Some(rtt))
+    }
+
+    /// Approximate memory held by DHT tables, bytes
+    pub fn memory_usage(&self) -> u64 {
+        let aux = self.allocated.bad_peers.load(Ordering::Relaxed) + 
+            self.allocated.disagreements.load(Ordering::Relaxed) + 
+            self.allocated.gossip.load(Ordering::Relaxed) + 
+            self.allocated.helpful.load(Ordering::Relaxed) + 
+            self.allocated.reliability.load(Ordering::Relaxed) + 
+            self.allocated.rtts.load(Ordering::Relaxed);
+        self.allocated.value_bytes.load(Ordering::Relaxed) + 
+            self.allocated.peers.load(Ordering::Relaxed) * Self::MEMORY_PEER_ENTRY + 
+            aux * Self::MEMORY_AUX_ENTRY
+    }
+
+    /// DHT key ID which nodes list of given overlay is stored under
+    pub fn overlay_nodes_key_id(&self, overlay_id: &Arc<OverlayShortId>) -> Result<DhtKeyId> {
+        let name = Self::overlay_nodes_name(self.config.compress_overlay_nodes);
+        self.value_key_id(&Self::dht_key_from_key_id(overlay_id, name))
+    }
+
+    /// Get share of successful queries to DHT peers
+    pub fn peer_reliability(&self) -> Vec<(Arc<KeyId>, f32)> {
+        self.reliability.iter().filter_map(
+            |entry| entry.val().reliability().map(|ratio| (entry.key().clone(), ratio))
+        ).collect()
+    }
+
+    /// Get smoothed round-trip times of measured DHT peers
+    pub fn peer_rtts(&self) -> Vec<(Arc<KeyId>, Duration)> {
+        self.rtts.iter().map(
+            |rtt| (rtt.key().clone(), Duration::from_micros(rtt.val().load(Ordering::Relaxed)))
+        ).collect()
+    }
+
+    /// Ping 
+    pub async fn ping(&self, dst: &Arc<KeyId>) -> Result<bool> {
+        let random_id = self.gen_nonce();
+        let query = TaggedTlObject {
+            object: TLObject::new(
+                DhtPing { 
+                    random_id 
+                }
+            ),
+            #[cfg(feature = "telemetry")]
+            tag: self.tag_dht_ping
+        };
+        let answer = self.query(dst, &query).await?;
+        let answer: DhtPongBoxed = if let Some(answer) = answer {
+            Query::parse(answer, &query.object)?
+        } else {
+            return Ok(false)
+        };
+        Ok(answer.random_id() == &random_id)
+    }
+
+    /// Ping with identity proof. DHT schema has no signed pong, so the proof is twofold: 
+    /// the nonce travels in ADNL packets encrypted to destination key, so only its owner 
+    /// can echo it, and the node signed by peer must verify under that very key
+    pub async fn ping_signed(&self, dst: &Arc<KeyId>) -> Result<bool> {
+        if !self.ping(dst).await? {
+            return Ok(false)
+        }
+        Ok(self.query_signed_node(dst, None).await?.is_some())
+    }
+
+    /// Re-propagate live values we hold to closest DHT peers, return number of stores made
+    pub async fn repair_replicas(dht: &Arc<Self>) -> Result<usize> {
+        let version = Version::get();
+        let values: Vec<(DhtKeyId, DhtValue)> = dht.storage.iter().filter_map(
+            |value| {
+                let object = &value.val().object;
+                (object.ttl > version).then(|| (*value.key(), object.clone()))
+            }
+        ).collect();
+        let mut ret = 0;
+        for (key_id, value) in values {
+            let mut iter = DhtIterator::with_key_id(dht, Arc::new(key_id));
+            let (wait, mut queue_reader) = Wait::new();
+            for _ in 0..Self::REPAIR_PEERS {
+                let Some((_, peer)) = iter.order.pop() else {
+                    break
+                };
+                let dht = dht.clone();
+                let value = value.clone();
+                let wait = wait.clone();
+                wait.request();
+                tokio::spawn(
+                    async move {
+                        match dht.store_to_peer(&peer, value).await {
+                            Ok(true) => wait.respond(Some(())),
+                            Ok(false) => wait.respond(None),
+                            Err(e) => {
+                                log::debug!(target: TARGET_STORE, "Replica repair error: {}", e);
+                                wait.respond(None)
+                            }
+                        }
+                    }
+                );
+            }
+            while let Some(stored) = wait.wait(&mut queue_reader, false).await {
+                if stored.is_some() {
+                    ret += 1
+                }
+            }
+        }
+        Ok(ret)
+    }
+
+    /// Find address of node with given key ID and register it as ADNL peer of our node key,
+    /// optionally checking it answers ping. Returns peer ID ready for ADNL queries
+    pub async fn resolve_and_register(
+        dht: &Arc<Self>, 
+        key_id: &Arc<KeyId>,
+        policy: DhtSearchPolicy,
+        check_reachable: bool
+    ) -> Result<Option<Arc<KeyId>>> {
+        let found = if check_reachable {
+            DhtNode::find_reachable_address_with_context(dht, key_id, &mut None, policy).await?
+        } else {
+            DhtNode::find_address_with_context(dht, key_id, &mut None, policy).await?
+        };
+        let Some((ip, key)) = found else {
+            log::debug!(target: TARGET_SEARCH, "Cannot resolve address of {}", key_id);
+            return Ok(None)
+        };
+        dht.adnl.add_peer(dht.node_key.id(), &ip, &key)
+    }
+
+    /// Trim auxiliary per-peer maps down to configured limits, return number of removed entries.
+    /// Entries of peers gone from routing table are dropped first; protected peers are kept
+    pub fn trim_aux_maps(&self) -> usize {
+        self.trim_peer_map(&self.bad_peers, &self.allocated.bad_peers, self.config.max_bad_peers) +
+        self.trim_peer_map(
+            &self.disagreements, 
+            &self.allocated.disagreements, 
+            self.config.max_bad_peers
+        ) +
+        self.expire_gossip() +
+        self.trim_peer_map(&self.gossip, &self.allocated.gossip, self.config.max_gossip_sources) +
+        self.trim_peer_map(&self.helpful, &self.allocated.helpful, self.config.max_helpful_peers) +
+        self.trim_peer_map(
+            &self.reliability, 
+            &self.allocated.reliability, 
+            self.config.max_peer_reliability
+        ) +
+        self.trim_peer_map(&self.rtts, &self.allocated.rtts, self.config.max_peer_rtts)
+    }
+
+    /// Get IDs of peers in routing table. The snapshot is taken without blocking 
+    /// table updates, so changes made concurrently may be partly reflected. 
+    /// Diff two snapshots to find churn: `later.difference(&earlier)` gives added peers, 
+    /// `earlier.difference(&later)` gives removed ones
+    pub fn routing_snapshot(&self) -> HashSet<Arc<KeyId>> {
+        let mut ret = HashSet::new();
+        for bucket in self.buckets.iter() {
+            for node in bucket.val().iter() {
+                ret.insert(node.key().clone());
+            }
+        }
+        ret
+    }
+
+    /// Serialized DhtQuery prefix with our signed node, for relaying queries on our behalf
+    pub fn query_prefix(&self) -> Result<Arc<Vec<u8>>> {
+        let prefix = self.query_prefix.read().map_err(
+            |_| error!("DHT query prefix lock is poisoned")
+        )?;
+        Ok(prefix.clone())
+    }
+
+    /// Re-sign local node with current address list and rebuild query prefix
+    pub fn refresh_local_node(&self) -> Result<()> {
+        let query = DhtQuery { 
+            node: self.sign_local_node()?
+        };
+        let mut prefix = Vec::new();
+        serialize_boxed_inplace(&mut prefix, &query)?;
+        *self.query_prefix.write().map_err(
+            |_| error!("DHT query prefix lock is poisoned")
+        )? = Arc::new(prefix);
+        Ok(())
+    }
+
+    /// Look for peers of sparse buckets: asks peers closest to random key of each 
+    /// sparse bucket up to deepest occupied one. Returns number of nodes received
+    pub async fn refresh_sparse_buckets(&self) -> Result<usize> {
+        let occupancy = self.bucket_occupancy();
+        let Some(deepest) = occupancy.iter().map(|(affinity, _)| *affinity).max() else {
+            return Ok(0)
+        };
+        let mut ret = 0;
+        for affinity in 0..=deepest {
+            let count = occupancy.iter()
+                .find_map(|(bucket, count)| (*bucket == affinity).then_some(*count))
+                .unwrap_or(0);
+            if count >= self.config.bucket_target_occupancy {
+                continue 
+            }
+            let key = self.random_key_in_bucket(affinity);
+            let mut iter = DhtIterator::with_key_id(self, Arc::new(key));
+            for _ in 0..Self::REFRESH_PEERS {
+                let Some((_, peer)) = iter.order.pop() else {
+                    break
+                };
+                match self.query_dht_nodes_toward(&peer, &key).await {
+                    Ok(Some(nodes)) => ret += nodes.len(),
+                    Ok(None) => (),
+                    Err(e) => log::debug!(
+                        target: TARGET_TABLE, 
+                        "Bucket {} refresh query to {} failed: {}", 
+                        affinity, peer, e
+                    )
+                }
+            }
+            log::debug!(
+                target: TARGET_TABLE, 
+                "Bucket {} refreshed, {} peers before", 
+                affinity, count
+            );
+        }
+        Ok(ret)
+    }
+
+    /// Save known DHT peers to routing table file
+    pub fn save_routing_table(&self, path: impl AsRef<Path>) -> Result<usize> {
+        let nodes = self.get_known_nodes(self.config.max_peers as usize)?;
+        let ret = nodes.len();
+        let mut data = Self::ROUTING_TABLE_MAGIC.to_vec();
+        data.extend_from_slice(&Self::ROUTING_TABLE_VERSION.to_le_bytes());
+        serialize_boxed_inplace(&mut data, &Nodes { nodes: nodes.into() }.into_boxed())?;
+        std::fs::write(path, data)?;
+        Ok(ret)
+    }
+
+    /// Start periodic republishing of own IP address under given key. Before each store 
+    /// the record is read back from network, and on_missing is fired if it has disappeared
+    pub fn start_address_republisher(
+        dht: &Arc<Self>, 
+        key: Arc<dyn KeyOption>, 
+        interval: Duration,
+        on_missing: Option<DhtRecordObserver>
+    ) -> DhtTaskHandle {
+        let owner = dht.clone();
+        let dht = Arc::downgrade(dht);
+        let task = tokio::spawn(
+            async move {
+                loop {
+                    tokio::time::sleep(interval).await;
+                    let Some(dht) = dht.upgrade() else {
+                        break
+                    };
+                    if let Some(on_missing) = &on_missing {
+                        match DhtNode::is_address_published(&dht, key.id()).await {
+                            Ok(true) => (),
+                            Ok(false) => {
+                                log::warn!(
+                                    target: TARGET_STORE, 
+                                    "Published address of {} disappeared from DHT", 
+                                    key.id()
+                                );
+                                on_missing(key.id(), "address")
+                            },
+                            Err(e) => log::warn!(
+                                target: TARGET_STORE, 
+                                "Cannot check published address of {}: {}", 
+                                key.id(), e
+                            )
+                        }
+                    }
+                    if let Err(e) = DhtNode::store_ip_address(&dht, &key).await {
+                        log::warn!(target: TARGET_STORE, "Cannot republish address: {}", e)
+                    }
+                }
+            }
+        );
+        owner.register_task(DhtTaskKind::AddressRepublisher, task)
+    }
+
+    /// Start periodic refresh of sparse buckets if enabled in config
+    pub fn start_bucket_refresh(dht: &Arc<Self>) -> Option<DhtTaskHandle> {
+        let interval = dht.config.bucket_refresh_interval?;
+        let owner = dht.clone();
+        let dht = Arc::downgrade(dht);
+        let task = tokio::spawn(
+            async move {
+                loop {
+                    tokio::time::sleep(interval).await;
+                    let Some(dht) = dht.upgrade() else {
+                        break
+                    };
+                    match dht.refresh_sparse_buckets().await {
+                        Ok(found) => log::debug!(
+                            target: TARGET_TABLE, 
+                            "Bucket refresh received {} nodes", 
+                            found
+                        ),
+                        Err(e) => log::warn!(target: TARGET_TABLE, "Bucket refresh error: {}", e)
+                    }
+                }
+            }
+        );
+        Some(owner.register_task(DhtTaskKind::BucketRefresh, task))
+    }
+
+    /// Start periodic re-signing of local node advertised in query prefix, if enabled 
+    /// in config, so its version keeps up with time
+    pub fn start_local_node_refresh(dht: &Arc<Self>) -> Option<DhtTaskHandle> {
+        let interval = dht.config.local_node_refresh_interval?;
+        let owner = dht.clone();
+        let dht = Arc::downgrade(dht);
+        let task = tokio::spawn(
+            async move {
+                loop {
+                    tokio::time::sleep(interval).await;
+                    let Some(dht) = dht.upgrade() else {
+                        break
+                    };
+                    if let Err(e) = dht.refresh_local_node() {
+                        log::warn!(target: TARGET_TABLE, "Cannot refresh local DHT node: {}", e)
+                    }
+                }
+            }
+        );
+        Some(owner.register_task(DhtTaskKind::LocalNodeRefresh, task))
+    }
+
+    /// Start periodic replica repair if enabled in config
+    pub fn start_replica_repair(dht: &Arc<Self>) -> Option<DhtTaskHandle> {
+        let interval = dht.config.replica_repair_interval?;
+        let owner = dht.clone();
+        let dht = Arc::downgrade(dht);
+        let task = tokio::spawn(
+            async move {
+                loop {
+                    tokio::time::sleep(interval).await;
+                    let Some(dht) = dht.upgrade() else {
+                        break
+                    };
+                    match DhtNode::repair_replicas(&dht).await {
+                        Ok(stored) => log::debug!(
+                            target: TARGET_STORE, 
+                            "Replica repair made {} stores", 
+                            stored
+                        ),
+                        Err(e) => log::warn!(target: TARGET_STORE, "Replica repair error: {}", e)
+                    }
+                }
+            }
+        );
+        Some(owner.register_task(DhtTaskKind::ReplicaRepair, task))
+    }
+
+    /// List background tasks of the node which are still running: (ID, kind)
+    pub fn spawned_tasks(&self) -> Vec<(u64, DhtTaskKind)> {
+        let mut ret = Vec::new();
+        for task in self.tasks.iter() {
+            let (kind, abort) = task.val();
+            if abort.is_finished() {
+                self.tasks.remove(task.key());
+            } else {
+                ret.push((*task.key(), *kind))
+            }
+        }
+        ret
+    }
+
+    /// Get consolidated snapshot of node state. Counts come from allocation 
+    /// counters, so values not yet garbage collected are included
+    pub fn stats(&self) -> DhtStats {
+        let (successes, total) = self.reliability.iter().fold(
+            (0, 0), 
+            |(successes, total), entry| {
+                let ok = entry.val().successes.load(Ordering::Relaxed);
+                let failed = entry.val().failures.load(Ordering::Relaxed);
+                (successes + ok, total + ok + failed)
+            }
+        );
+        DhtStats {
+            known_peers: self.known_peers.count(),
+            max_peers: self.config.max_peers,
+            table_peers: self.allocated.table_peers.load(Ordering::Relaxed),
+            buckets: self.bucket_occupancy(),
+            bad_peers: self.allocated.bad_peers.load(Ordering::Relaxed),
+            values: self.allocated.values.load(Ordering::Relaxed),
+            value_bytes: self.allocated.value_bytes.load(Ordering::Relaxed),
+            query_success_rate: (total > 0).then(|| successes as f32 / total as f32)
+        }
+    }
+
+    /// Check own node is discoverable: store own address, then resolve it back from 
+    /// network with fresh search. The search starts from peers, skipping local storage, 
+    /// so the result reflects propagation to other replicas
+    pub async fn self_discovery_check(dht: &Arc<Self>) -> Result<bool> {
+        let Some(addr) = AdnlNode::parse_address_list(&dht.adnl.build_address_list(None)?)? else {
+            log::warn!(target: TARGET_STORE, "Self discovery check: no usable local address");
+            return Ok(false)
+        };
+        DhtNode::store_ip_address(dht, &dht.node_key).await?;
+        // Search with iterator in place does not take value from local storage
+        let key_id = Arc::new(
+            dht.value_key_id(&Self::dht_key_from_key_id(dht.node_key.id(), "address"))?
+        );
+        let mut ctx = Some(
+            AddressSearchContext {
+                budget: None,
+                iter: Some(DhtIterator::with_key_id(dht, key_id.clone())),
+                key_id
+            }
+        );
+        let found = DhtNode::find_address_with_context(
+            dht, 
+            dht.node_key.id(), 
+            &mut ctx, 
+            DhtSearchPolicy::FullSearch(Self::MAX_TASKS)
+        ).await?;
+        match found {
+            Some((ip, _)) if ip == addr => Ok(true),
+            Some((ip, _)) => {
+                log::warn!(
+                    target: TARGET_SEARCH, 
+                    "Self discovery check: found address {}, expected {}", 
+                    ip, addr
+                );
+                Ok(false)
+            },
+            None => {
+                log::warn!(target: TARGET_SEARCH, "Self discovery check: own address not found");
+                Ok(false)
+            }
+        }
+    }
+
+    /// Visit all live values in local storage, yielding to runtime periodically
+    pub async fn scan_storage(&self, mut visit: impl FnMut(&DhtKeyId, &DhtValue)) {
+        let version = Version::get();
+        for (i, key) in self.storage_keys().iter().enumerate() {
+            Self::yield_on_scan(i).await;
+            let Some(value) = self.storage.get(key) else {
+                continue
+            };
+            if value.val().object.ttl > version {
+                visit(key, &value.val().object)
+            }
+        }
+    }
+
+    /// Remove expired values from local storage, return number of removed ones
+    pub async fn storage_gc(&self) -> usize {
+        let version = Version::get();
+        let mut ret = 0;
+        for (i, key) in self.storage_keys().iter().enumerate() {
+            Self::yield_on_scan(i).await;
+            match self.storage.get(key) {
+                Some(value) if value.val().object.ttl <= version => (),
+                _ => continue
+            }
+            if let Some(removed) = self.storage.remove(key) {
+                let size = Self::value_size(&removed.val().object);
+                self.allocated.value_bytes.fetch_sub(size, Ordering::Relaxed);
+                self.published.remove(key);
+                ret += 1
+            }
+        }
+        ret
+    }
+
+    /// Get number and total payload size of live values in local storage
+    pub async fn storage_size(&self) -> (usize, usize) {
+        let mut ret = (0, 0);
+        self.scan_storage(
+            |_, value| {
+                ret.0 += 1;
+                ret.1 += value.value.len();
+            }
+        ).await;
+        ret
+    }
+
+    /// Store own IP address under several keys concurrently, returns per-key outcomes
+    pub async fn store_ip_addresses(
+        dht: &Arc<Self>, 
+        keys: &[Arc<dyn KeyOption>], 
+        concurrency: usize
+    ) -> Vec<(Arc<KeyId>, Result<bool>)> {
+        let limiter = Arc::new(Semaphore::new(concurrency.max(1)));
+        let mut tasks = Vec::with_capacity(keys.len());
+        for key in keys {
+            let dht = dht.clone();
+            let key = key.clone();
+            let limiter = limiter.clone();
+            let task = tokio::spawn(
+                async move {
+                    let _permit = limiter.acquire_owned().await.map_err(
+                        |e| error!("Cannot acquire store permit: {}", e)
+                    )?;
+                    DhtNode::store_ip_address(&dht, &key).await
+                }
+            );
+            tasks.push((key.id().clone(), task))
+        }
+        let mut ret = Vec::with_capacity(tasks.len());
+        for (key_id, task) in tasks {
+            let res = match task.await {
+                Ok(res) => res,
+                Err(e) => Err(error!("Store task for key {} failed: {}", key_id, e))
+            };
+            ret.push((key_id, res))
+        }
+        ret
+    }
+
+    /// Store own IP address under keys of given ADNL key tags, returns per-tag outcomes
+    pub async fn store_ip_address_for_tags(
+        dht: &Arc<Self>, 
+        key_tags: &[usize]
+    ) -> Vec<(usize, Result<bool>)> {
+        // Outcomes are slotted by tag position, so they come back in input order
+        let mut slots: Vec<Option<Result<bool>>> = Vec::with_capacity(key_tags.len());
+        let mut keys = Vec::new();
+        let mut positions = Vec::new();
+        for (i, key_tag) in key_tags.iter().enumerate() {
+            match dht.adnl.key_by_tag(*key_tag) {
+                Ok(key) => {
+                    keys.push(key);
+                    positions.push(i);
+                    slots.push(None)
+                },
+                Err(e) => slots.push(Some(Err(e)))
+            }
+        }
+        let stored = Self::store_ip_addresses(dht, &keys, keys.len()).await;
+        for (i, (_, res)) in positions.into_iter().zip(stored) {
+            slots[i] = Some(res)
+        }
+        key_tags.iter().zip(slots).map(
+            |(key_tag, res)| {
+                let res = res.unwrap_or_else(
+                    || Err(error!("INTERNAL ERROR: no store outcome for key tag {}", key_tag))
+                );
+                (*key_tag, res)
+            }
+        ).collect()
+    }
+
+    /// Store own IP address
+    pub async fn store_ip_address(dht: &Arc<Self>, key: &Arc<dyn KeyOption>) -> Result<bool> {
+        let outcome = Self::store_ip_address_with_outcome(dht, key).await?;
+        Ok(outcome == DhtAddressStoreOutcome::Stored)
+    }
+
+    /// Store own IP address, telling missing local address apart from failed store
+    pub async fn store_ip_address_with_outcome(
+        dht: &Arc<Self>, 
+        key: &Arc<dyn KeyOption>
+    ) -> Result<DhtAddressStoreOutcome> {
+        log::debug!(target: TARGET_STORE, "Storing key ID {}", key.id());
+        let addr_list = dht.adnl.build_address_list(None)?;
+        let Some(addr) = AdnlNode::parse_address_list(&addr_list)? else {
+            log::warn!(
+                target: TARGET_STORE, 
+                "No usable local address to store for key ID {} yet", 
+                key.id()
+            );
+            return Ok(DhtAddressStoreOutcome::NoLocalAddress)
+        };
+        let value = serialize_boxed(&addr_list.into_boxed())?;
+        let value = Self::sign_value("address", value, key)?;
+        let key = Self::dht_key_from_key_id(key.id(), "address");
+        let key_id = dht.value_key_id(&key)?;
+        log::debug!(target: TARGET_STORE, "Storing DHT key ID {}", base64_encode(&key_id[..]));
+        dht.store_own_value(key_id, value.clone())?;
+        let stored = Self::store_value(
+            dht,
+            key,
+            value,
+            |object| object.is::<AddressListBoxed>(),
+            false, 
+            |mut objects| {
+                while let Some((_, object)) = objects.pop() {
+                    if let Ok(addr_list) = object.downcast::<AddressListBoxed>() {
+                        let addr_list = addr_list.only();
+                        if let Some(ip) = AdnlNode::parse_address_list(&addr_list)? {
+                            if ip == addr { //dht.adnl.ip_address() {
+                                log::debug!(target: TARGET_STORE, "Checked stored address {:?}", ip);
+                                return Ok(true);
+                            } else {
+                                log::warn!(
+                                    target: TARGET_STORE, 
+                                    "Found another stored address {:?}, expected {:?}", 
+                                    ip,
+                                    dht.adnl.ip_address()
+                                )
+                            }
+                        } else {
+                            log::warn!(
+                                target: TARGET_STORE, 
+                                "Found some wrong address list {:?}",
+                                addr_list
+                            )
+                        }
+                    } else {
+                        fail!("INTERNAL ERROR: address list type mismatch in store")
+                    }
+                }
+                Ok(false)
+            }
+        ).await?;
+        if stored {
+            dht.address_stored.store(true, Ordering::Relaxed);
+            Ok(DhtAddressStoreOutcome::Stored)
+        } else {
+            Ok(DhtAddressStoreOutcome::Unconfirmed)
+        }
+    }
+
+    /// Retire value stored under given key and name by its empty successor.
+    /// DHT has no delete: the tombstone is an empty value signed with a fresh (higher) ttl,
+    /// so it supersedes the old value on replicas it reaches. Replicas that never receive
+    /// the tombstone keep serving the old value until its original ttl expires. 
+    pub async fn store_tombstone(
+        dht: &Arc<Self>, 
+        key: &Arc<dyn KeyOption>, 
+        name: &str
+    ) -> Result<bool> {
+        log::debug!(target: TARGET_STORE, "Storing tombstone for key ID {}, name {}", key.id(), name);
+        let value = Self::sign_value(name, Vec::new(), key)?;
+        let key = Self::dht_key_from_key_id(key.id(), name);
+        let key_id = Arc::new(dht.value_key_id(&key)?);
+        dht.store_own_value(*key_id, value.clone())?;
+        let quorum = dht.config.store_quorum.max(1);
+        let stored = if quorum == 1 {
+            Self::store_value(
+                dht,
+                key,
+                value,
+                |_| true,
+                false, 
+                // Retired when no replica returns a non-empty value anymore
+                |objects| Ok(objects.is_empty())
+            ).await?
+        } else {
+            // Replicas holding tombstone return no value, so readback cannot count them.
+            // They are counted by tombstone check below instead
+            Self::store_value_with_outcome(dht, key, value, |_| true, false, |_| Ok(false)).await?;
+            true
+        };
+        // Missing values may mean no replica answered at all
+        if stored {
+            Self::confirm_tombstone(dht, &key_id, quorum).await
+        } else {
+            Ok(false)
+        }
+    }
+
+    /// Store signed overlay-level value (e.g. config or bootstrap hints). 
+    /// Overlay public key cannot sign, so the value is signed by operator key 
+    /// and namespaced by overlay ID in the DHT key name. Value must be serialized boxed TL object
+    pub async fn store_overlay_value(
+        dht: &Arc<Self>, 
+        overlay_id: &Arc<OverlayShortId>,
+        name: &str,
+        value: Vec<u8>,
+        key: &Arc<dyn KeyOption>
+    ) -> Result<bool> {
+        let name = Self::overlay_value_name(overlay_id, name);
+        log::debug!(target: TARGET_STORE, "Storing overlay value {} for key ID {}", name, key.id());
+        deserialize_boxed(&value)?;
+        let value = Self::sign_value(&name, value, key)?;
+        let key = Self::dht_key_from_key_id(key.id(), &name);
+        dht.store_own_value(dht.value_key_id(&key)?, value.clone())?;
+        let expected = value.value.clone();
+        Self::store_value(
+            dht,
+            key,
+            value,
+            |_| true,
+            false, 
+            move |mut objects| {
+                while let Some((_, object)) = objects.pop() {
+                    if serialize_boxed(&object)? == *expected {
+                        return Ok(true)
+                    }
+                }
+                Ok(false)
+            }
+        ).await
+    }
+
+    /// Store value under caller-chosen DHT key (id, idx, name), signed by key owning DHT key ID. 
+    /// Value must be serialized boxed TL object
+    pub async fn store_signed(
+        dht: &Arc<Self>, 
+        dht_key: DhtKey,
+        value: Vec<u8>,
+        ttl: i32,
+        key: &Arc<dyn KeyOption>
+    ) -> Result<bool> {
+        let outcome = Self::store_signed_with_outcome(dht, dht_key, value, ttl, key).await?;
+        Ok(outcome.is_durable())
+    }
+
+    /// Store value as store_signed does, reporting number of replicas confirmed 
+    /// against configured store quorum
+    pub async fn store_signed_with_outcome(
+        dht: &Arc<Self>, 
+        dht_key: DhtKey,
+        value: Vec<u8>,
+        ttl: i32,
+        key: &Arc<dyn KeyOption>
+    ) -> Result<DhtStoreOutcome> {
+        log::debug!(
+            target: TARGET_STORE, 
+            "Storing signed value for key ID {}, name {:?}, idx {}", 
+            key.id(), dht_key.name, dht_key.idx
+        );
+        deserialize_boxed(&value)?;
+        let value = DhtValueBuilder::with_key(dht_key.clone())
+            .with_ttl(ttl)
+            .with_value(value)
+            .build_signed(key)?;
+        dht.store_own_value(dht.value_key_id(&dht_key)?, value.clone())?;
+        let expected = value.value.clone();
+        Self::store_value_with_outcome(
+            dht,
+            dht_key,
+            value,
+            |_| true,
+            false, 
+            move |mut objects| {
+                while let Some((_, object)) = objects.pop() {
+                    if serialize_boxed(&object)? == *expected {
+                        return Ok(true)
+                    }
+                }
+                Ok(false)
+            }
+        ).await
+    }
+
+    /// Store value to given DHT peer
+    pub async fn store_to_peer(&self, peer: &Arc<KeyId>, value: DhtValue) -> Result<bool> {
+        let query = TaggedTlObject {
+            object: TLObject::new(
+                Store {
+                    value
+                }
+            ),
+            #[cfg(feature = "telemetry")]
+            tag: self.tag_store
+        };
+        self.store_with_query(peer, &query).await
+    }
+
+    /// Store own overlay node
+    pub async fn store_overlay_node(
+        dht: &Arc<Self>, 
+        overlay_id: &OverlayId, 
+        node: &OverlayNode
+    ) -> Result<bool> {
+        Self::store_overlay_node_with_ttl(
+            dht, 
+            overlay_id, 
+            node, 
+            Version::get() + Self::TIMEOUT_VALUE
+        ).await
+    }
+
+    /// Store own overlay node with given absolute expiration time, 
+    /// so overlay membership lifetime is tuned apart from address records
+    pub async fn store_overlay_node_with_ttl(
+        dht: &Arc<Self>, 
+        overlay_id: &OverlayId, 
+        node: &OverlayNode,
+        ttl: i32
+    ) -> Result<bool> {
+        log::debug!(target: TARGET_STORE, "Storing overlay node {:?}, ttl {}", node, ttl);
+        let overlay_short_id = OverlayShortId::from_data(
+            hash(Overlay { name: overlay_id.to_vec().into() })?
+        );
+        OverlayUtils::verify_node(&overlay_short_id, node)?;
+        let compressed = dht.config.compress_overlay_nodes;
+        let value = DhtValueBuilder::with_key_id(
+                &overlay_short_id, 
+                Self::overlay_nodes_name(compressed)
+            )
+            .with_ttl(ttl)
+            .with_value(Self::serialize_overlay_nodes(vec![node.clone()], compressed)?)
+            .build_overlay_nodes(overlay_id)?;
+        let key = value.key.key.clone();
+        dht.store_own_value(dht.value_key_id(&key)?, value.clone())?;
+        Self::store_value(
+            dht,
+            key,
+            value,
+            |object| object.is::<OverlayNodesBoxed>(),
+            true, 
+            |mut objects| {
+                while let Some((_, object)) = objects.pop() {
+                    if let Ok(nodes_list) = object.downcast::<OverlayNodesBoxed>() {
+                        for found_node in nodes_list.only().nodes.0 {
+                            if &found_node == node {
+                                log::debug!(target: TARGET_STORE, "Checked stored node {:?}", node);
+                                return Ok(true);
+                            }
+                        }
+                    } else {
+                        fail!("INTERNAL ERROR: overlay nodes list type mismatch in store")
+                    }
+                }
+                Ok(false)
+            }
+        ).await
+    }
+
+    /// Get counts of DHT peers returning values contradicting replica quorum 
+    pub fn value_disagreements(&self) -> Vec<(Arc<KeyId>, u64)> {
+        self.disagreements.iter().map(
+            |entry| (entry.key().clone(), entry.val().load(Ordering::Relaxed))
+        ).collect()
+    }
+
+    /// Get histogram of live value payload sizes. Each bucket is (upper bound, count),
+    /// bounds are given ascending; values above the last bound go to (usize::MAX, count) 
+    pub async fn value_size_histogram(&self, bounds: &[usize]) -> Vec<(usize, usize)> {
+        let mut ret: Vec<(usize, usize)> = bounds.iter().map(|bound| (*bound, 0)).collect();
+        ret.push((usize::MAX, 0));
+        self.scan_storage(
+            |_, value| {
+                let size = value.value.len();
+                if let Some(bucket) = ret.iter_mut().find(|(bound, _)| size <= *bound) {
+                    bucket.1 += 1
+                }
+            }
+        ).await;
+        ret
+    }
+
+    /// Remaining TTL of locally stored live value, in seconds
+    pub fn value_ttl_remaining(&self, key: &DhtKeyId) -> Option<i32> {
+        let version = Version::get();
+        let value = self.storage.get(key)?;
+        let ttl = value.val().object.ttl;
+        if ttl > version {
+            Some(ttl - version)
+        } else {
+            None
+        }
+    }
+
+    /// Verify overlay nodes against overlay ID the same way DHT does on store
+    pub fn verify_overlay_nodes(
+        overlay_id: &Arc<OverlayShortId>, 
+        nodes: &[OverlayNode]
+    ) -> Vec<Result<()>> {
+        nodes.iter().map(|node| OverlayUtils::verify_node(overlay_id, node)).collect()
+    }
+
+    /// Watch members of overlay with given ID, refreshing them periodically 
+    /// and reporting changes since previous refresh
+    pub fn watch_overlay_members(
+        dht: &Arc<Self>, 
+        overlay_id: Arc<OverlayShortId>,
+        interval: Duration,
+        on_change: DhtOverlayMembersObserver
+    ) -> DhtTaskHandle {
+        let owner = dht.clone();
+        let dht = Arc::downgrade(dht);
+        let task = tokio::spawn(
+            async move {
+                let mut members = HashSet::new();
+                // Context is kept across polls with its resolve state, but each poll
+                // has to report full membership, so nodes reported before are forgotten
+                let mut ctx = None;
+                loop {
+                    let Some(node) = dht.upgrade() else {
+                        break
+                    };
+                    if let Some(ctx) = &mut ctx {
+                        ctx.forget_stored()
+                    }
+                    let found = DhtNode::find_overlay_nodes_impl(
+                        &node,
+                        &overlay_id,
+                        &mut ctx,
+                        DhtSearchPolicy::FullSearch(Self::MAX_TASKS),
+                        usize::MAX,
+                        None,
+                        &mut None
+                    ).await;
+                    drop(node);
+                    match found {
+                        Ok(found) if found.is_empty() => log::debug!(
+                            target: TARGET_SEARCH, 
+                            "No members of overlay {} found, keep previous view", 
+                            overlay_id
+                        ),
+                        Ok(found) => {
+                            let mut added = Vec::new();
+                            let mut current = HashSet::new();
+                            for (ip, member) in found {
+                                let key: Arc<dyn KeyOption> = match (&member.id).try_into() {
+                                    Ok(key) => key,
+                                    Err(_) => continue
+                                };
+                                if !members.contains(key.id()) {
+                                    added.push((ip, member))
+                                }
+                                current.insert(key.id().clone());
+                            }
+                            let removed: Vec<Arc<KeyId>> = 
+                                members.difference(&current).cloned().collect();
+                            members = current;
+                            if !added.is_empty() || !removed.is_empty() {
+                                on_change(added, removed)
+                            }
+                        },
+                        Err(e) => log::warn!(
+                            target: TARGET_SEARCH, 
+                            "Cannot refresh members of overlay {}: {}", 
+                            overlay_id, e
+                        )
+                    }
+                    tokio::time::sleep(interval).await;
+                }
+            }
+        );
+        owner.register_task(DhtTaskKind::OverlayMembersWatch, task)
+    }
+
+    fn add_gossip_peers(&self, source: &Arc<KeyId>, nodes: &[Node]) -> Result<()> {
+        let Some(limit) = self.config.max_gossip_peers else {
+            for node in nodes {
+                self.add_peer(node)?;
+            }
+            return Ok(())
+        };
+        if add_unbound_object_to_map(
+            &self.gossip, 
+            source.clone(), 
+            || Ok(AuxEntry::with_value(AtomicU64::new(0)))
+        )? {
+            let count = self.allocated.gossip.fetch_add(1, Ordering::Relaxed) + 1;
+            if count > self.config.max_gossip_sources as u64 {
+                self.trim_aux_maps();
+            }
+        }
+        let now = Version::get() as u32 as u64;
+        let window = self.config.gossip_window.as_secs();
+        let mut allowed = 0;
+        if let Some(gossip) = self.gossip.get(source) {
+            gossip.val().touch();
+            gossip.val().fetch_update(
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+                |packed| {
+                    let (start, used) = if now.saturating_sub(packed >> 32) >= window {
+                        (now, 0)
+                    } else {
+                        (packed >> 32, packed & 0xFFFFFFFF)
+                    };
+                    allowed = (limit as u64).saturating_sub(used).min(nodes.len() as u64);
+                    Some((start << 32) | (used + allowed))
+                }
+            ).ok();
+        }
+        let allowed = allowed as usize;
+        for node in &nodes[..allowed] {
+            self.add_peer(node)?;
+        }
+        if allowed < nodes.len() {
+            log::debug!(
+                target: TARGET_TABLE, 
+                "Dropped {} of {} nodes suggested by {}: gossip limit reached", 
+                nodes.len() - allowed, nodes.len(), source
+            );
+        }
+        Ok(())
+    }
+
+    fn account_value_size(&self, (old_size, new_size): (u64, u64)) {
+        self.allocated.value_bytes.fetch_add(new_size, Ordering::Relaxed);
+        self.allocated.value_bytes.fetch_sub(old_size, Ordering::Relaxed);
+        self.enforce_memory_limit(None)
+    }
+
+    fn check_replica_consistency(&self, answers: Vec<(Arc<KeyId>, i32, Vec<u8>)>) -> Result<()> {
+        if answers.len() < Self::MIN_QUORUM {
+            return Ok(())
+        }
+        // Replicas with older copies are lagging rather than lying: 
+        // only copies of the freshest version vote
+        let Some(freshest) = answers.iter().map(|(_, ttl, _)| *ttl).max() else {
+            return Ok(())
+        };
+        let answers: Vec<_> = answers.into_iter()
+            .filter(|(_, ttl, _)| *ttl == freshest)
+            .map(|(peer, _, value)| (peer, value))
+            .collect();
+        let mut votes: Vec<(&Vec<u8>, usize)> = Vec::new();
+        for (_, value) in answers.iter() {
+            if let Some((_, count)) = votes.iter_mut().find(|(voted, _)| *voted == value) {
+                *count += 1
+            } else {
+                votes.push((value, 1))
+            }
+        }
+        let Some((quorum, count)) = votes.iter().max_by_key(|(_, count)| *count) else {
+            return Ok(())
+        };
+        if *count * 2 <= answers.len() {
+            // No majority, nobody to blame
+            return Ok(())
+        }
+        for (peer, value) in answers.iter() {
+            if value == *quorum {
+                continue
+            }
+            if add_unbound_object_to_map(
+                &self.disagreements,
+                peer.clone(),
+                || Ok(AuxEntry::with_value(AtomicU64::new(0)))
+            )? {
+                self.allocated.disagreements.fetch_add(1, Ordering::Relaxed);
+            }
+            let Some(disagreements) = self.disagreements.get(peer) else {
+                continue
+            };
+            disagreements.val().touch();
+            let disagreements = disagreements.val().fetch_add(1, Ordering::Relaxed) + 1;
+            log::debug!(
+                target: TARGET_SEARCH, 
+                "DHT peer {} disagrees with replica quorum, {} times", 
+                peer, disagreements
+            );
+            if disagreements >= Self::MAX_DISAGREEMENTS {
+                self.set_query_result(None, peer)?;
+            }
+        }
+        if self.allocated.disagreements.load(Ordering::Relaxed) > self.config.max_bad_peers as u64 {
+            self.trim_aux_maps();
+        }
+        Ok(())
+    }
+
+    async fn compare_value_with(&self, peer: &Arc<KeyId>, key: &DhtKeyId) -> Result<DhtKeyDiff> {
+        let local = self.search_dht_key(key);
+        let remote = self.probe_value(peer, key).await?;
+        // Compressed lists may deflate differently, so values are compared decoded. 
+        // Empty one is a tombstone, equal to another tombstone only
+        let value_differs = match (&local, &remote) {
+            (Some(local), Some(remote)) => match (local.value.is_empty(), remote.value.is_empty()) {
+                (true, true) => false,
+                (false, false) => serialize_boxed(&Self::deserialize_value(local)?)? != 
+                    serialize_boxed(&Self::deserialize_value(remote)?)?,
+                _ => true
+            },
+            _ => false
+        };
+        let ret = DhtKeyDiff {
+            key: *key,
+            local_ttl: local.map(|local| local.ttl),
+            remote_ttl: remote.map(|remote| remote.ttl),
+            value_differs
+        };
+        Ok(ret)
+    }
+
+    // At least quorum of closest replicas must answer, and none may return a live value
+    async fn confirm_tombstone(
+        dht: &Arc<Self>, 
+        key_id: &Arc<DhtKeyId>, 
+        quorum: usize
+    ) -> Result<bool> {
+        let query = TaggedTlObject {
+            object: TLObject::new(
+                FindValue { 
+                    key: UInt256::from_slice(&key_id[..]),
+                    k: 6 
+                }
+            ),
+            #[cfg(feature = "telemetry")]
+            tag: dht.tag_find_value
+        };
+        let mut iter = DhtIterator::with_key_id(dht, key_id.clone());
+        let mut answered = 0;
+        for _ in 0..quorum.max(Self::MAX_TASKS as usize) {
+            let Some((_, peer)) = iter.order.pop() else {
+                break
+            };
+            let answer = match dht.query(&peer, &query).await {
+                Ok(Some(answer)) => answer,
+                Ok(None) => continue,
+                Err(e) => {
+                    log::debug!(target: TARGET_STORE, "Tombstone check on {} failed: {}", peer, e);
+                    continue
+                }
+            };
+            let answer: DhtValueResult = Query::parse(answer, &query.object)?;
+            if let DhtValueResult::Dht_ValueFound(value) = answer {
+                if !value.value.only().value.is_empty() {
+                    return Ok(false)
+                }
+            }
+            answered += 1
+        }
+        log::debug!(
+            target: TARGET_STORE, 
+            "Tombstone for DHT key ID {} checked on {} replicas", 
+            base64_encode(&key_id[..]), answered
+        );
+        Ok(answered >= quorum)
+    }
+
+    // Source policy is checked by caller, once per inbound query
+    fn consume_query(&self, object: TLObject, peers: &AdnlPeers) -> Result<QueryResult> {
+        let object = match object.downcast::<DhtPing>() {
+            Ok(query) => {
+                self.observe_query(peers, self.tag_dht_ping);
+                return QueryResult::consume(
+                    self.process_ping(&query)?, 
+                    #[cfg(feature = "telemetry")]
+                    None
+                )
+            },
+            Err(object) => object
+        };
+        let object = match object.downcast::<FindNode>() {
+            Ok(query) => {
+                self.observe_query(peers, self.tag_find_node);
+                return QueryResult::consume(
+                    self.process_find_node(&query)?, 
+                    #[cfg(feature = "telemetry")]
+                    None
+                )
+            },
+            Err(object) => object
+        };
+        let object = match object.downcast::<FindValue>() {
+            Ok(query) => {
+                self.observe_query(peers, self.tag_find_value);
+                return QueryResult::consume_boxed(
+                    self.process_find_value(&query)?, 
+                    #[cfg(feature = "telemetry")]
+                    None
+                )
+            },
+            Err(object) => object
+        };
+        let object = match object.downcast::<GetSignedAddressList>() {
+            Ok(_) => {
+                self.observe_query(peers, self.tag_get_signed_address_list);
+                return QueryResult::consume(
+                    self.get_signed_node()?, 
+                    #[cfg(feature = "telemetry")]
+                    None
+                )
+            },
+            Err(object) => object
+        };
+        match object.downcast::<Store>() {
+            Ok(query) => {
+                self.observe_query(peers, self.tag_store);
+                QueryResult::consume_boxed(
+                    self.process_store(query)?, 
+                    #[cfg(feature = "telemetry")]
+                    None
+                )
+            },
+            Err(object) => {
+                log::warn!(target: TARGET, "Unexpected DHT query {:?}", object);
+                Ok(QueryResult::Rejected(object))
+            }        
+        }
+    }
+
+    fn deflate(data: &[u8]) -> Result<Vec<u8>> {
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data)?;
+        Ok(encoder.finish()?)
+    }
+
+    fn deserialize_overlay_nodes(value: &[u8], compressed: bool) -> Result<Vec<OverlayNode>> {
+        let object = if compressed {
+            deserialize_boxed(&Self::inflate(value)?)?
+        } else {
+            deserialize_boxed(value)?
+        };
+        let nodes = object
+            .downcast::<OverlayNodesBoxed>()
+            .map_err(|object| error!("Wrong OverlayNodes: {:?}", object))?;
+        Ok(nodes.only().nodes.0)
+    }
+
+    // Key of value is checked to match queried DHT key ID by this time, 
+    // so its name tells whether payload is compressed
+    fn deserialize_value(value: &DhtValue) -> Result<TLObject> {
+        let name: &[u8] = &value.key.key.name;
+        let compressed = 
+            matches!(value.key.update_rule, UpdateRule::Dht_UpdateRule_OverlayNodes) && 
+            (name == Self::overlay_nodes_name(true).as_bytes());
+        if compressed {
+            deserialize_boxed(&Self::inflate(&value.value)?)
+        } else {
+            deserialize_boxed(&value.value)
+        }
+    }
+
+    fn check_key_type(&self, key: &Arc<dyn KeyOption>) -> Result<()> {
+        if let Some(accepted) = &self.config.accepted_key_types {
+            if !accepted.contains(&key.type_id()) {
+                fail!("DHT key type {} is not accepted", key.type_id())
+            }
+        }
+        Ok(())
+    }
+
+    fn check_query_source(&self, object: &TLObject, source: &Arc<KeyId>, known: bool) -> Result<()> {
+        let policy = &self.config.source_policy;
+        let restricted = (policy.store && object.is::<Store>()) || 
+            (policy.find_value && object.is::<FindValue>());
+        if restricted && !known {
+            fail!("DHT query from unknown source {} refused", source)
+        }
+        Ok(())
+    }
+
+    fn dht_key_from_key_id(id: &Arc<KeyId>, name: &str) -> DhtKey {
+        DhtKey {
+            id: UInt256::with_array(*id.data()),
+            idx: 0,
+            name: name.as_bytes().to_vec().into()
+        }
+    }
+
+    async fn find_address_impl(
+        dht: &Arc<Self>, 
+        key_id: &Arc<KeyId>,
+        ctx_opt: &mut Option<AddressSearchContext>,
+        policy: DhtSearchPolicy,
+        strict: bool,
+        reachable: bool,
+        budget: Option<u32>
+    ) -> Result<Option<ResolvedAddress>> {
+        if ctx_opt.is_none() {
+            let key_id = Arc::new(dht.value_key_id(&Self::dht_key_from_key_id(key_id, "address"))?);
+            ctx_opt.replace(
+                AddressSearchContext {
+                    budget,
+                    iter: None,
+                    key_id
+                }
+            );
+        }
+        let Some(ctx) = ctx_opt else {
+            fail!("INTERNAL ERROR: cannot make address search context")
+        };
+        let mut unreachable = Vec::new();
+        loop {
+            let options = SearchOptions {
+                all: false,
+                budget: &mut ctx.budget,
+                policy: &policy,
+                strict,
+                trace: None
+            };
+            let mut addr_list = DhtNode::find_value_entries(
+                dht,
+                &ctx.key_id,
+                |object| object.is::<AddressListBoxed>(),
+                options,
+                &mut ctx.iter
+            ).await?;
+            let Some(found) = addr_list.pop() else {
+                return Ok(None)
+            };
+            let ttl = found.ttl;
+            let (ip, key) = Self::parse_value_as_address(found.key, found.object)?;
+            if !reachable {
+                return Ok(Some(ResolvedAddress::with_params(ip, key, ttl)))
+            }
+            if !unreachable.contains(&ip) {
+                if dht.is_reachable(&ip, &key).await? {
+                    return Ok(Some(ResolvedAddress::with_params(ip, key, ttl)))
+                }
+                log::debug!(target: TARGET_SEARCH, "Address {} of {} is unreachable", ip, key.id());
+                unreachable.push(ip)
+            }
+            if ctx.iter.is_none() {
+                // Search is over
+                return Ok(None)
+            }
+        }
+    }
+
+    async fn find_overlay_nodes_impl(
         dht: &Arc<Self>, 
         overlay_id: &Arc<OverlayShortId>,
         ctx_search_opt: &mut Option<OverlayNodesSearchContext>,
         policy: DhtSearchPolicy,
+        target_count: usize,
+        max_resolve: Option<usize>,
         iter: &mut Option<DhtIterator>
     ) -> Result<Vec<(IpAddress, OverlayNode)>> {
         let mut ret = Vec::new();
+        let mut attempted = 0;
         if ctx_search_opt.is_none() {
-            let key_id = Arc::new(hash(Self::dht_key_from_key_id(overlay_id, "nodes"))?);
-            ctx_search_opt.replace(
-                OverlayNodesSearchContext {
-                    key_id,
-                    search: VecDeque::new(),
-                    stored: AddressCache::with_limit(Self::MAX_PEERS)
-                }
-            );
+            ctx_search_opt.replace(OverlayNodesSearchContext::with_params(dht, overlay_id, 0)?);
         }
         let Some(ctx_search) = ctx_search_opt else {
             fail!("INTERNAL ERROR: cannot make overlay search context")
         };
         log::debug!(
-            target: TARGET, 
+            target: TARGET_SEARCH, 
             "-------- Overlay nodes search, {}", 
             if let Some(iter) = iter {
                 iter.to_string()
@@ -485,14 +3433,21 @@ impl DhtNode {
             }
         );
         let mut postponed = VecDeque::new();
+        let mut tasks = SearchTasks(Vec::new());
         loop {
             if ctx_search.search.is_empty() {
+                let options = SearchOptions {
+                    all: true,
+                    budget: &mut None,
+                    policy: &policy,
+                    strict: false,
+                    trace: None
+                };
                 let mut nodes_lists = DhtNode::find_value(
                     dht,
                     &ctx_search.key_id,
                     |object| object.is::<OverlayNodesBoxed>(),
-                    &policy,
-                    true, 
+                    options,
                     iter
                 ).await?;
                 if nodes_lists.is_empty() {
@@ -519,32 +3474,43 @@ impl DhtNode {
             }
             let (wait, mut queue_reader) = Wait::new();
             log::debug!(
-                target: TARGET, 
+                target: TARGET_SEARCH, 
                 "-------- Overlay nodes search, {} ({} suspicious) nodes to resolve", 
                 ctx_search.search.len() + postponed.len(), 
                 postponed.len()
             );
             let limit = match &policy {
                 DhtSearchPolicy::FastSearch(_) => 1,
-                DhtSearchPolicy::FullSearch(limit) => *limit
+                DhtSearchPolicy::FullSearch(limit) => *limit,
+                DhtSearchPolicy::LimitedSearch(limit, _) => *limit
             };
-            while let Some(mut ctx_resolve) = ctx_search.search.pop_front() {
+            while max_resolve.map_or(true, |max_resolve| attempted < max_resolve) {
+                let Some(mut ctx_resolve) = ctx_search.search.pop_front() else {
+                    break
+                };
                 if ctx_search.stored.contains(ctx_resolve.key.id()) {
                     log::trace!(
-                        target: TARGET, 
+                        target: TARGET_SEARCH, 
                         "-------- Overlay nodes search, node {} already stored", 
                         ctx_resolve.key.id()
                     );
                     continue
                 }
+                attempted += 1;
                 let dht = dht.clone();
                 let policy = policy.clone();
+                let limiter = ctx_search.resolve_limiter.clone();
                 let wait = wait.clone();
                 let reqs = wait.request_immediate();
-                tokio::spawn(
+                let task = tokio::spawn(
                     async move {
+                        let _permit = if let Some(limiter) = &limiter {
+                            Some(limiter.acquire().await)
+                        } else {
+                            None
+                        };
                         log::trace!(
-                            target: TARGET, 
+                            target: TARGET_SEARCH, 
                             "-------- Overlay nodes search, try resolve node {}", 
                             ctx_resolve.key.id()
                         );
@@ -556,7 +3522,7 @@ impl DhtNode {
                         ).await {
                             Ok(Some((ip, _))) => {
                                 log::debug!(
-                                    target: TARGET, 
+                                    target: TARGET_SEARCH, 
                                     "-------- Overlay nodes search, resolved {} IP: {}, key: {}",
                                     ctx_resolve.key.id(), ip, 
                                     base64_encode(ctx_resolve.key.pub_key().unwrap_or(&[0u8; 32]))
@@ -565,7 +3531,7 @@ impl DhtNode {
                             },
                             Ok(None) => {
                                 log::trace!(
-                                    target: TARGET, 
+                                    target: TARGET_SEARCH, 
                                     "-------- Overlay nodes search, {} not resolved", 
                                     ctx_resolve.key.id()
                                 );
@@ -573,7 +3539,7 @@ impl DhtNode {
                             },
                             Err(e) => {
                                 log::debug!(
-                                    target: TARGET, 
+                                    target: TARGET_SEARCH, 
                                     "-------- Overlay nodes search, cannot resolve {}: {}", 
                                     ctx_resolve.key.id(), e
                                 );
@@ -582,6 +3548,7 @@ impl DhtNode {
                         }
                     }
                 );
+                tasks.0.push(task);
                 if reqs >= limit as usize {
                     break
                 }
@@ -590,258 +3557,143 @@ impl DhtNode {
                 match wait.wait(&mut queue_reader, false).await { 
                     Some(Some((None, ctx_resolve))) => match &policy {
                         DhtSearchPolicy::FastSearch(_) => (), 
-                        DhtSearchPolicy::FullSearch(_) => postponed.push_back(ctx_resolve),
+                        DhtSearchPolicy::FullSearch(_) | 
+                        DhtSearchPolicy::LimitedSearch(..) => postponed.push_back(ctx_resolve)
                     },
                     Some(Some((Some(ip), ctx_resolve))) => {
                         if ctx_search.stored.put(ctx_resolve.key.id().clone())? {
                             ret.push((ip, ctx_resolve.node));
                         }
-                    },
-                    _ => break
-                }
-            }
-            log::debug!(
-                target: TARGET, 
-                "-------- Overlay nodes search, so far resolved {} nodes", 
-                ret.len()
-            );
-            if !ret.is_empty() {
-                // Found some
-                break
-            }
-            if iter.is_none() {
-                // Search is over
-                break
-            }
-        }
-        ctx_search.search.append(&mut postponed);
-        log::debug!(
-            target: TARGET, 
-            "-------- Overlay nodes search, {} nodes yet to resolve", 
-            ctx_search.search.len()
-        );
-        Ok(ret)
-    }
-
-    /// Get DHT peer via iterator
-    pub fn get_known_peer(&self, iter: &mut Option<AddressCacheIterator>) -> Option<Arc<KeyId>> {
-        loop {
-            let ret = if let Some(iter) = iter {
-                self.known_peers.next(iter)
-            } else {
-                let (new_iter, first) = self.known_peers.first();
-                iter.replace(new_iter);
-                first
-            };
-            if let Some(peer) = &ret {
-                if let Some(count) = self.bad_peers.get(peer) {
-                    if count.val().load(Ordering::Relaxed) >= Self::MAX_FAIL_COUNT {
-                        continue
-                    }
-                }
-            }
-            break ret
-        }
-    }
-
-    /// Get known DHT nodes
-    pub fn get_known_nodes(&self, limit: usize) -> Result<Vec<Node>> {
-        if limit == 0 {
-            fail!("It is useless to ask for zero known nodes")
-        }
-        let mut ret = Vec::new();
-        for i in 0..=255 {
-            if let Some(bucket) = self.buckets.get(&i) {
-                for node in bucket.val().iter() {         
-                    ret.push(node.val().object.clone());
-                    if ret.len() == limit {
-                        return Ok(ret)
-                    }
-                }
-            }
-        }
-        Ok(ret)
-    }
-                    
-    /// Get signed address list 
-    pub async fn get_signed_address_list(&self, dst: &Arc<KeyId>) -> Result<bool> {
-        let query = TaggedTlObject {
-            object: TLObject::new(GetSignedAddressList),
-            #[cfg(feature = "telemetry")]
-            tag: self.tag_get_signed_address_list
-        };
-        let answer = self.query_with_prefix(dst, &query).await?;
-        let answer: NodeBoxed = if let Some(answer) = answer {
-            Query::parse(answer, &query.object)?
-        } else {
-            return Ok(false)
-        };
-        self.add_peer(&answer.only())?;
-        Ok(true)
-    }
-
-    /// Get signed node
-    pub fn get_signed_node(&self) -> Result<Node> {
-        self.sign_local_node()
-    }
-
-    /// Node IP address
-    pub fn ip_address(&self) -> &IpAddress {
-        self.adnl.ip_address()
-    }
-
-    /// Node key
-    pub fn key(&self) -> &Arc<dyn KeyOption> {
-        &self.node_key
-    }
-
-    /// Ping 
-    pub async fn ping(&self, dst: &Arc<KeyId>) -> Result<bool> {
-        let random_id = rand::thread_rng().gen();
-        let query = TaggedTlObject {
-            object: TLObject::new(
-                DhtPing { 
-                    random_id 
-                }
-            ),
-            #[cfg(feature = "telemetry")]
-            tag: self.tag_dht_ping
-        };
-        let answer = self.query(dst, &query).await?;
-        let answer: DhtPongBoxed = if let Some(answer) = answer {
-            Query::parse(answer, &query.object)?
-        } else {
-            return Ok(false)
-        };
-        Ok(answer.random_id() == &random_id)
-    }
-
-    /// Store own IP address
-    pub async fn store_ip_address(dht: &Arc<Self>, key: &Arc<dyn KeyOption>) -> Result<bool> {
-        log::debug!(target: TARGET, "Storing key ID {}", key.id());
-        let addr_list = dht.adnl.build_address_list(None)?;
-        let addr = AdnlNode::parse_address_list(&addr_list)?.ok_or_else(
-            || error!("INTERNAL ERROR: cannot parse generated address list")
-        )?;
-        let value = serialize_boxed(&addr_list.into_boxed())?;
-        let value = Self::sign_value("address", value, key)?;
-        let key = Self::dht_key_from_key_id(key.id(), "address");
-        let key_id = hash(key.clone())?;
-        log::debug!(target: TARGET, "Storing DHT key ID {}", base64_encode(&key_id[..]));
-        dht.process_store_signed_value(key_id, value.clone())?;
-        Self::store_value(
-            dht,
-            key,
-            value,
-            |object| object.is::<AddressListBoxed>(),
-            false, 
-            |mut objects| {
-                while let Some((_, object)) = objects.pop() {
-                    if let Ok(addr_list) = object.downcast::<AddressListBoxed>() {
-                        let addr_list = addr_list.only();
-                        if let Some(ip) = AdnlNode::parse_address_list(&addr_list)? {
-                            if ip == addr { //dht.adnl.ip_address() {
-                                log::debug!(target: TARGET, "Checked stored address {:?}", ip);
-                                return Ok(true);
-                            } else {
-                                log::warn!(
-                                    target: TARGET, 
-                                    "Found another stored address {:?}, expected {:?}", 
-                                    ip,
-                                    dht.adnl.ip_address()
-                                )
-                            }
-                        } else {
-                            log::warn!(
-                                target: TARGET, 
-                                "Found some wrong address list {:?}",
-                                addr_list
-                            )
-                        }
-                    } else {
-                        fail!("INTERNAL ERROR: address list type mismatch in store")
-                    }
-                }
-                Ok(false)
-            }
-        ).await
-    }
-
-    /// Store own overlay node
-    pub async fn store_overlay_node(
-        dht: &Arc<Self>, 
-        overlay_id: &OverlayId, 
-        node: &OverlayNode
-    ) -> Result<bool> {
-        log::debug!(target: TARGET, "Storing overlay node {:?}", node);
-        let overlay_id = Overlay {
-            name: overlay_id.to_vec().into()
-        };
-        let overlay_short_id = OverlayShortId::from_data(hash(overlay_id.clone())?);
-        OverlayUtils::verify_node(&overlay_short_id, node)?;
-        let nodes = OverlayNodes {
-            nodes: vec![node.clone()].into()
-        }.into_boxed();
-        let key = Self::dht_key_from_key_id(&overlay_short_id, "nodes");
-        let value = DhtValue {
-            key: DhtKeyDescription {
-                id: overlay_id.into_boxed(),
-                key: key.clone(),
-                signature: Default::default(),
-                update_rule: UpdateRule::Dht_UpdateRule_OverlayNodes
-            },
-            ttl: Version::get() + Self::TIMEOUT_VALUE,
-            signature: Default::default(),
-            value: serialize_boxed(&nodes)?.into()
-        };
-        dht.process_store_overlay_nodes(hash(key.clone())?, value.clone())?;
-        Self::store_value(
-            dht,
-            key,
-            value,
-            |object| object.is::<OverlayNodesBoxed>(),
-            true, 
-            |mut objects| {
-                while let Some((_, object)) = objects.pop() {
-                    if let Ok(nodes_list) = object.downcast::<OverlayNodesBoxed>() {
-                        for found_node in nodes_list.only().nodes.0 {
-                            if &found_node == node {
-                                log::debug!(target: TARGET, "Checked stored node {:?}", node);
-                                return Ok(true);
-                            }
-                        }
-                    } else {
-                        fail!("INTERNAL ERROR: overlay nodes list type mismatch in store")
-                    }
+                    },
+                    _ => break
                 }
-                Ok(false)
             }
-        ).await
+            log::debug!(
+                target: TARGET_SEARCH, 
+                "-------- Overlay nodes search, so far resolved {} nodes", 
+                ret.len()
+            );
+            if ret.len() >= target_count {
+                // Found enough
+                break
+            }
+            if max_resolve.map_or(false, |max_resolve| attempted >= max_resolve) {
+                // Resolve budget of the call is spent
+                break
+            }
+            if iter.is_none() && ctx_search.search.is_empty() {
+                // Search is over
+                break
+            }
+        }
+        ctx_search.search.append(&mut postponed);
+        log::debug!(
+            target: TARGET_SEARCH, 
+            "-------- Overlay nodes search, {} nodes yet to resolve", 
+            ctx_search.search.len()
+        );
+        // Lowest latency members first, members without RTT data keep resolution order
+        ret.sort_by_cached_key(
+            |(_, node)| {
+                let key: Result<Arc<dyn KeyOption>> = (&node.id).try_into();
+                key.ok()
+                    .and_then(|key| dht.rtts.get(key.id()).map(|rtt| rtt.val().load(Ordering::Relaxed)))
+                    .unwrap_or(u64::MAX)
+            }
+        );
+        Ok(ret)
     }
 
-    fn deserialize_overlay_nodes(value: &[u8]) -> Result<Vec<OverlayNode>> {
-        let nodes = deserialize_boxed(value)?
-            .downcast::<OverlayNodesBoxed>()
-            .map_err(|object| error!("Wrong OverlayNodes: {:?}", object))?;
-        Ok(nodes.only().nodes.0)
+    /// Find signed overlay-level value published by overlay operator with given key ID
+    pub async fn find_overlay_value(
+        dht: &Arc<Self>, 
+        overlay_id: &Arc<OverlayShortId>,
+        name: &str,
+        key_id: &Arc<KeyId>
+    ) -> Result<Option<TLObject>> {
+        let name = Self::overlay_value_name(overlay_id, name);
+        let key_id = Arc::new(dht.value_key_id(&Self::dht_key_from_key_id(key_id, &name))?);
+        let options = SearchOptions {
+            all: false,
+            budget: &mut None,
+            policy: &DhtSearchPolicy::FullSearch(Self::MAX_TASKS),
+            strict: true,
+            trace: None
+        };
+        let mut values = DhtNode::find_value(dht, &key_id, |_| true, options, &mut None).await?;
+        Ok(values.pop().map(|(_, object)| object))
     }
 
-    fn dht_key_from_key_id(id: &Arc<KeyId>, name: &str) -> DhtKey {
-        DhtKey {
-            id: UInt256::with_array(*id.data()),
-            idx: 0,
-            name: name.as_bytes().to_vec().into()
+    async fn find_value<R: Into<DhtValueCheck>>(
+        dht: &Arc<Self>, 
+        key_id: &Arc<DhtKeyId>, 
+        check: impl Fn(&TLObject) -> R + Copy + Send + 'static,
+        options: SearchOptions<'_>,
+        iter_opt: &mut Option<DhtIterator>
+    ) -> Result<Vec<(DhtKeyDescription, TLObject)>> {
+        let found = DhtNode::find_value_entries(dht, key_id, check, options, iter_opt).await?;
+        Ok(found.into_iter().map(|found| (found.key, found.object)).collect())
+    }
+
+    async fn find_value_entries<R: Into<DhtValueCheck>>(
+        dht: &Arc<Self>, 
+        key_id: &Arc<DhtKeyId>, 
+        check: impl Fn(&TLObject) -> R + Copy + Send + 'static,
+        options: SearchOptions<'_>,
+        iter_opt: &mut Option<DhtIterator>
+    ) -> Result<Vec<FoundValue>> {
+        // Value held locally is taken once, when search starts
+        let mut local = None;
+        if iter_opt.is_none() {
+            if let Some(value) = dht.search_dht_key(key_id) {
+                // Strict search takes only values not about to expire, as from peers
+                let fresh = !options.strict || 
+                    (value.ttl > Version::get() + Self::MIN_TTL_LEFT);
+                if fresh && !value.value.is_empty() {
+                    let object = Self::deserialize_value(&value)?;
+                    if check(&object).into() != DhtValueCheck::Reject {
+                        let found = FoundValue {
+                            key: value.key,
+                            object,
+                            peer: dht.node_key.id().clone(),
+                            stop: false,
+                            ttl: value.ttl
+                        };
+                        if !options.all {
+                            log::debug!(
+                                target: TARGET_SEARCH, 
+                                "FindValue with DHT key ID {} query, found in local storage", 
+                                base64_encode(&key_id[..])
+                            );
+                            // Search resumed by caller goes on to peers
+                            iter_opt.replace(DhtIterator::with_key_id(dht, key_id.clone()));
+                            return Ok(vec![found])
+                        }
+                        local = Some(found)
+                    }
+                }
+            }
         }
+        let found = DhtNode::find_value_with_peers(
+            dht, key_id, check, options, iter_opt
+        ).await?;
+        let mut ret = Vec::with_capacity(found.len() + 1);
+        ret.extend(local);
+        ret.extend(found);
+        Ok(ret)
     }
 
-    async fn find_value(
+    async fn find_value_with_peers<R: Into<DhtValueCheck>>(
         dht: &Arc<Self>, 
         key_id: &Arc<DhtKeyId>, 
-        check: impl Fn(&TLObject) -> bool + Copy + Send + 'static,
-        policy: &DhtSearchPolicy,
-        all: bool,
+        check: impl Fn(&TLObject) -> R + Copy + Send + 'static,
+        options: SearchOptions<'_>,
         iter_opt: &mut Option<DhtIterator>
-    ) -> Result<Vec<(DhtKeyDescription, TLObject)>> {
+    ) -> Result<Vec<FoundValue>> {
+        let SearchOptions { all, budget, policy, strict, mut trace } = options;
+        if dht.config.fail_on_no_peers && (dht.known_peers.count() == 0) {
+            return Err(DhtError::NoPeers.into())
+        }
         let iter = iter_opt.get_or_insert_with(||DhtIterator::with_key_id(dht, key_id.clone()));
         if &iter.key_id != key_id {
             fail!("INTERNAL ERROR: DHT key mismatch in value search")
@@ -858,54 +3710,121 @@ impl DhtNode {
             tag: dht.tag_find_value
         };
         let key_dumper = DhtKeyIdDumper::with_params(log::Level::Debug, key_id);
-        let query = Arc::new(query);
+        let peers_queried = Arc::new(AtomicU64::new(0));
+        let _search = dht.register_search(key_id, policy, peers_queried.clone());
+        let query = ValueQuery {
+            key_id: key_id.clone(),
+            query: Arc::new(query),
+            strict
+        };
         let (wait, mut queue_reader) = Wait::new();  
+        let mut tasks = SearchTasks(Vec::new());
         let mut known_peers = dht.known_peers.count();
+        let mut known_failures = dht.query_failures.load(Ordering::Relaxed);
         log::debug!(
-            target: TARGET, 
+            target: TARGET_SEARCH, 
             "FindValue with DHT key ID {} query, {}", 
             key_dumper, iter
         );
-        let limit = match &policy {
-            DhtSearchPolicy::FastSearch(limit) => *limit,
-            DhtSearchPolicy::FullSearch(limit) => *limit
-        } as usize;
+        let (limit, max_rounds) = match &policy {
+            DhtSearchPolicy::FastSearch(limit) => (*limit, None),
+            DhtSearchPolicy::FullSearch(limit) => (*limit, None),
+            DhtSearchPolicy::LimitedSearch(limit, max_rounds) => (*limit, Some(*max_rounds))
+        };
+        let limit = limit as usize;
+        let mut rounds = 0;
+        let mut hedged = 0;
+        let mut answers = Vec::new();
         loop {
-            while let Some((_, peer)) = iter.order.pop() {
-                let dht_cloned = dht.clone();
-                let key_id = key_id.clone();
-                let peer = peer.clone(); 
-                let query = query.clone(); 
-                let wait = wait.clone(); 
-                let reqs = wait.request_immediate(); 
-                tokio::spawn(
-                    async move {
-                        match dht_cloned.value_query(&peer, &query, &key_id, check).await {
-                            Ok(found) => wait.respond(found),
-                            Err(e) => {
-                                log::warn!(target: TARGET, "ERROR: {}", e);
-                                wait.respond(None)
-                            }
-                        } 
-                    } 
-                );
+            while budget.map_or(true, |budget| budget > 0) {
+                let Some((_, peer)) = iter.order.pop() else {
+                    break
+                };
+                if let Some(budget) = budget.as_mut() {
+                    *budget -= 1
+                }
+                peers_queried.fetch_add(1, Ordering::Relaxed);
+                if let Some(trace) = trace.as_mut() {
+                    trace.queried.push(peer.clone())
+                }
+                let reqs = Self::spawn_value_query(dht, peer, &query, check, &wait, &mut tasks);
                 if reqs >= limit {
                     break;
                 } 
             } 
             log::debug!(
-                target: TARGET, 
+                target: TARGET_SEARCH, 
                 "FindValue with DHT key ID {} query, {} parallel reqs, {}", 
                 key_dumper, wait.count(), iter
             );
             let mut finished = match &policy {
                 DhtSearchPolicy::FastSearch(_) => true,
-                DhtSearchPolicy::FullSearch(_) => false
+                DhtSearchPolicy::FullSearch(_) | DhtSearchPolicy::LimitedSearch(..) => false
             };
             loop {
-                match wait.wait(&mut queue_reader, !all).await { 
+                let found = match dht.config.hedge_delay {
+                    Some(delay) if hedged < dht.config.max_hedged_queries => {
+                        let found = tokio::time::timeout(
+                            delay, 
+                            wait.wait(&mut queue_reader, !all)
+                        ).await;
+                        if let Ok(found) = found {
+                            found
+                        } else {
+                            // Slow peers: duplicate the query to next best peer
+                            let peer = if budget.map_or(true, |budget| budget > 0) {
+                                iter.order.pop()
+                            } else {
+                                None
+                            };
+                            if let Some((_, peer)) = peer {
+                                if let Some(budget) = budget.as_mut() {
+                                    *budget -= 1
+                                }
+                                peers_queried.fetch_add(1, Ordering::Relaxed);
+                                if let Some(trace) = trace.as_mut() {
+                                    trace.queried.push(peer.clone())
+                                }
+                                Self::spawn_value_query(
+                                    dht, peer, &query, check, &wait, &mut tasks
+                                );
+                                hedged += 1;
+                            } else {
+                                hedged = dht.config.max_hedged_queries;
+                            }
+                            continue
+                        }
+                    },
+                    _ => wait.wait(&mut queue_reader, !all).await
+                };
+                match found { 
                     Some(None) => (),
-                    Some(Some(val)) => ret.push(val),
+                    Some(Some(found)) => {
+                        // Only single-valued records are comparable across replicas, 
+                        // overlay node lists legitimately differ
+                        let single = matches!(
+                            found.key.update_rule,
+                            UpdateRule::Dht_UpdateRule_Signature | UpdateRule::Dht_UpdateRule_Anybody
+                        );
+                        if all && single {
+                            answers.push(
+                                (found.peer.clone(), found.ttl, serialize_boxed(&found.object)?)
+                            )
+                        }
+                        if let Some(trace) = trace.as_mut() {
+                            trace.responder.get_or_insert_with(|| found.peer.clone());
+                        }
+                        let stop = found.stop;
+                        ret.push(found);
+                        if stop {
+                            log::debug!(
+                                target: TARGET_SEARCH, 
+                                "FindValue with DHT key ID {} query, stop on authoritative value", 
+                                key_dumper
+                            );
+                            finished = true
+                        }
+                    },
                     None => finished = true
                 }
                 // Update iterator if required
@@ -915,39 +3834,507 @@ impl DhtNode {
                         iter.update(dht);
                         known_peers = updated_known_peers;
                     }
+                    // Failed queries changed peer scores, re-rank pending peers
+                    let updated_failures = dht.query_failures.load(Ordering::Relaxed);
+                    if updated_failures != known_failures {
+                        iter.rescore(dht);
+                        known_failures = updated_failures;
+                    }
+                }
+                // Add more tasks if required 
+                if !all || (ret.len() < limit) || finished {
+                    break
+                }
+            }
+            // Stop if possible 
+            if (all && (ret.len() >= limit)) || (!all && !ret.is_empty()) || finished {
+                break
+            } 
+            rounds += 1;
+            if let Some(max_rounds) = max_rounds {
+                if rounds >= max_rounds {
+                    log::debug!(
+                        target: TARGET_SEARCH, 
+                        "FindValue with DHT key ID {} query, stop after {} rounds", 
+                        key_dumper, rounds
+                    );
+                    break
+                }
+            }
+        }
+        if iter.order.is_empty() {
+            iter_opt.take();
+        }
+        dht.check_replica_consistency(answers)?;
+        Ok(ret)
+    }
+
+    fn enforce_memory_limit(&self, keep: Option<&Arc<KeyId>>) {
+        let Some(limit) = self.config.max_memory else {
+            return
+        };
+        let usage = self.memory_usage();
+        if usage <= limit {
+            return
+        }
+        // Own values and kept peers may leave nothing to evict, so such pass is not 
+        // repeated on each insert
+        let now = Version::get() as u32 as u64;
+        let failed = self.memory_trim_failed.load(Ordering::Relaxed);
+        if (failed > 0) && (now < failed + Self::MEMORY_TRIM_RETRY) {
+            return
+        }
+        // Trim down to low-water mark, so the heavy pass does not repeat on each insert. 
+        // Values and peers give up their shares of excess
+        let target = limit - limit / Self::MEMORY_TRIM_SHARE;
+        let excess = usage - target;
+        let value_bytes = self.allocated.value_bytes.load(Ordering::Relaxed).min(usage);
+        let values_excess = (excess as u128 * value_bytes as u128 / usage as u128) as u64;
+        let peers_excess = excess - values_excess;
+        let mut evicted_values = 0;
+        if values_excess > 0 {
+            // Soonest expiring values go first, own published ones stay
+            let mut values: Vec<(i32, DhtKeyId)> = self.storage.iter().filter_map(
+                |value| {
+                    let key = value.key();
+                    self.published.get(key).is_none().then(|| (value.val().object.ttl, *key))
+                }
+            ).collect();
+            values.sort_unstable();
+            let mut freed = 0;
+            for (_, key) in values {
+                if freed >= values_excess {
+                    break
+                }
+                if let Some(removed) = self.storage.remove(&key) {
+                    let size = Self::value_size(&removed.val().object);
+                    self.allocated.value_bytes.fetch_sub(size, Ordering::Relaxed);
+                    freed += size;
+                    evicted_values += 1
+                }
+            }
+        }
+        // Worst scored peers. Removed peers are released lazily, so their number 
+        // is estimated upfront rather than re-checked against usage
+        let mut to_evict = (peers_excess + Self::MEMORY_PEER_ENTRY - 1) / Self::MEMORY_PEER_ENTRY;
+        let mut evicted_peers = 0;
+        while to_evict > 0 {
+            let Some((affinity, peer, _)) = self.find_eviction_candidate(keep) else {
+                break
+            };
+            self.evict_peer(affinity, &peer);
+            evicted_peers += 1;
+            to_evict -= 1
+        }
+        if (evicted_values == 0) && (evicted_peers == 0) {
+            self.memory_trim_failed.store(now, Ordering::Relaxed);
+            log::debug!(
+                target: TARGET_TABLE, 
+                "DHT memory usage {} over ceiling {}, nothing to evict", 
+                usage, limit
+            );
+            return
+        }
+        self.memory_trim_failed.store(0, Ordering::Relaxed);
+        log::info!(
+            target: TARGET_TABLE, 
+            "DHT memory usage {} over ceiling {}, evicted {} values and {} peers", 
+            usage, limit, evicted_values, evicted_peers
+        );
+    }
+
+    // AddressCache has no removal, so evicted peer stays in known peers until the ring
+    // overwrites it. Peer iterators skip such entries as not found in buckets, 
+    // and re-received peer is put into the bucket again
+    fn evict_peer(&self, affinity: u8, peer: &Arc<KeyId>) {
+        if let Some(bucket) = self.buckets.get(&affinity) {
+            if bucket.val().remove(peer).is_some() {
+                self.allocated.table_peers.fetch_sub(1, Ordering::Relaxed);
+                self.churn.evicted.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        if self.bad_peers.remove(peer).is_some() {
+            self.allocated.bad_peers.fetch_sub(1, Ordering::Relaxed);
+        }
+        if self.disagreements.remove(peer).is_some() {
+            self.allocated.disagreements.fetch_sub(1, Ordering::Relaxed);
+        }
+        if self.gossip.remove(peer).is_some() {
+            self.allocated.gossip.fetch_sub(1, Ordering::Relaxed);
+        }
+        if self.helpful.remove(peer).is_some() {
+            self.allocated.helpful.fetch_sub(1, Ordering::Relaxed);
+        }
+        if self.reliability.remove(peer).is_some() {
+            self.allocated.reliability.fetch_sub(1, Ordering::Relaxed);
+        }
+        if self.rtts.remove(peer).is_some() {
+            self.allocated.rtts.fetch_sub(1, Ordering::Relaxed);
+        }
+        log::debug!(target: TARGET_TABLE, "Evicted DHT peer {} with affinity {}", peer, affinity);
+    }
+
+    // Drop gossip windows which are over: their sources may have gone silent
+    fn expire_gossip(&self) -> usize {
+        let now = Version::get() as u32 as u64;
+        let window = self.config.gossip_window.as_secs();
+        let mut ret = 0;
+        for entry in self.gossip.iter() {
+            let start = entry.val().load(Ordering::Relaxed) >> 32;
+            if now.saturating_sub(start) < window {
+                continue
+            }
+            if self.gossip.remove(entry.key()).is_some() {
+                self.allocated.gossip.fetch_sub(1, Ordering::Relaxed);
+                ret += 1
+            }
+        }
+        ret
+    }
+
+    fn find_eviction_candidate(&self, keep: Option<&Arc<KeyId>>) -> Option<(u8, Arc<KeyId>, u8)> {
+        // Failing peers first wherever they are, then the farthest one, 
+        // worst scored among equally far ones
+        let rank = |affinity: u8, score: u8| {
+            (score < Self::MAX_FAIL_COUNT, affinity, std::cmp::Reverse(score))
+        };
+        let mut ret: Option<(u8, Arc<KeyId>, u8)> = None;
+        for affinity in 0..=255 {
+            let Some(bucket) = self.buckets.get(&affinity) else {
+                continue
+            };
+            for node in bucket.val().iter() {
+                if self.protected_peers.get(node.key()).is_some() || (keep == Some(node.key())) {
+                    continue
+                }
+                let score = if let Some(score) = self.bad_peers.get(node.key()) {
+                    score.val().load(Ordering::Relaxed)
+                } else {
+                    0
+                };
+                match &ret {
+                    Some((top_affinity, _, top_score)) 
+                        if rank(*top_affinity, *top_score) <= rank(affinity, score) => (),
+                    _ => ret = Some((affinity, node.key().clone(), score))
+                }
+            }
+        }
+        ret
+    }
+
+    fn gen_nonce(&self) -> i64 {
+        if let Some(nonce_source) = &self.config.nonce_source {
+            nonce_source()
+        } else {
+            rand::thread_rng().gen()
+        }
+    }
+
+    fn get_affinity(key1: &DhtKeyId, key2: &DhtKeyId) -> u8 {
+        let mut ret = 0;
+        for i in 0..32 {
+            match key1[i] ^ key2[i] {
+                0 => ret += 8,
+                x => {
+                    if (x & 0xF0) == 0 {
+                        ret += Self::BITS[(x & 0x0F) as usize] + 4
+                    } else {
+                        ret += Self::BITS[(x >> 4) as usize]
+                    }
+                    break
+                }
+            }
+        }
+        ret
+    }
+
+    async fn is_reachable(&self, ip: &IpAddress, key: &Arc<dyn KeyOption>) -> Result<bool> {
+        let Some(peer) = self.adnl.add_peer(self.node_key.id(), ip, key)? else {
+            return Ok(false)
+        };
+        let random_id = self.gen_nonce();
+        let query = TaggedTlObject {
+            object: TLObject::new(
+                DhtPing { 
+                    random_id 
+                }
+            ),
+            #[cfg(feature = "telemetry")]
+            tag: self.tag_dht_ping
+        };
+        // Query via ADNL directly: candidate is not a DHT peer to score 
+        let peers = AdnlPeers::with_keys(self.node_key.id().clone(), peer);
+        let answer = if let Some(answer) = self.adnl.clone().query(&query, &peers, None).await? {
+            answer
+        } else {
+            return Ok(false)
+        };
+        let answer: DhtPongBoxed = Query::parse(answer, &query.object)?;
+        Ok(answer.random_id() == &random_id)
+    }
+
+    fn helpful_score(packed: u64, now: i32) -> u64 {
+        let updated = packed >> 32;
+        let halvings = (now as u32 as u64).saturating_sub(updated) / Self::HELPFUL_HALF_LIFE;
+        (packed & 0xFFFFFFFF).checked_shr(halvings.min(63) as u32).unwrap_or(0)
+    }
+
+    fn is_reliable_peer(&self, peer: &Arc<KeyId>) -> bool {
+        let Some(stats) = self.reliability.get(peer) else {
+            return false
+        };
+        let stats = stats.val();
+        let total = stats.successes.load(Ordering::Relaxed) + 
+            stats.failures.load(Ordering::Relaxed);
+        (total >= Self::MIN_RELIABILITY_SAMPLES) && 
+            stats.reliability().map_or(false, |ratio| ratio >= Self::MIN_RELIABILITY)
+    }
+
+    // Any node gets into routing table by its own query prefix, so peer in table is known 
+    // only once it has answered some query of this node
+    fn is_known_source(&self, source: &Arc<KeyId>) -> bool {
+        if self.config.source_policy.allowlist.contains(source) {
+            return true
+        }
+        self.is_peer_in_table(source) && self.reliability.get(source).map_or(
+            false, 
+            |stats| stats.val().successes.load(Ordering::Relaxed) > 0
+        )
+    }
+
+    fn is_peer_in_table(&self, peer: &Arc<KeyId>) -> bool {
+        let affinity = Self::get_affinity(self.node_key.id().data(), peer.data());
+        self.is_peer_in_bucket(affinity, peer)
+    }
+
+    fn insert_peer(&self, peer: &Node, persist: bool) -> Result<Option<Arc<KeyId>>> {
+        if let Err(e) = self.verify_other_node(peer) {
+            log::warn!(target: TARGET_TABLE, "Error when verifying DHT peer: {}", e);
+            return Ok(None)
+        }
+        let peer_key: Arc<dyn KeyOption> = (&peer.id).try_into()?;
+        if self.is_own_key(peer_key.id()) {
+            log::debug!(target: TARGET_TABLE, "Skip own node as DHT peer");
+            return Ok(None)
+        }
+        let addr = if let Some(addr) = AdnlNode::parse_address_list(&peer.addr_list)? {
+            addr
+        } else {
+            log::warn!(target: TARGET_TABLE, "Wrong DHT peer address {:?}", peer.addr_list);
+            return Ok(None)
+        };
+        let ret = self.adnl.add_peer(self.node_key.id(), &addr, &peer_key)?;
+        let ret = if let Some(ret) = ret {
+            ret
+        } else {
+            return Ok(None)
+        };
+        let affinity = Self::get_affinity(self.node_key.id().data(), ret.data());
+        let in_table = self.is_peer_in_bucket(affinity, &ret);
+        if !in_table && !self.make_room_for_peer(affinity) {
+            log::debug!(
+                target: TARGET_TABLE, 
+                "DHT peer table is full, drop peer {} with affinity {}", 
+                ret, affinity
+            );
+            return Ok(None)
+        }
+        let outdated = in_table && self.is_peer_outdated_in_bucket(affinity, &ret, peer);
+        if self.known_peers.put(ret.clone())? || !in_table || outdated {
+            add_unbound_object_to_map(
+                &self.buckets, 
+                affinity, 
+                || Ok(lockfree::map::Map::new())
+            )?;
+            if let Some(bucket) = self.buckets.get(&affinity) {
+                let updated = std::cell::Cell::new(false);
+                let stored = add_counted_object_to_map_with_update(
+                    bucket.val(),
+                    ret.clone(), 
+                    |old_node| {
+                        if let Some(old_node) = old_node {
+                            if old_node.object.version > peer.version {
+                                return Ok(None)
+                            }
+                            if old_node.object.version == peer.version {
+                                if old_node.object.addr_list == peer.addr_list {
+                                    return Ok(None)
+                                }
+                                // Rejected update comes again with every nodes list, logged quietly
+                                let accepted = self.config.accept_same_version_updates;
+                                log::log!(
+                                    target: TARGET_TABLE, 
+                                    if accepted { log::Level::Warn } else { log::Level::Debug },
+                                    "DHT peer {} changed address at same version: {:?} -> {:?}", 
+                                    ret, old_node.object.addr_list, peer.addr_list
+                                );
+                                if !accepted {
+                                    return Ok(None)
+                                }
+                            }
+                        }
+                        updated.set(old_node.is_some());
+                        let ret = NodeObject {
+                            object: peer.clone(),
+                            counter: self.allocated.peers.clone().into()
+                        };
+                        #[cfg(feature = "telemetry")]
+                        if let Some(telemetry) = &self.telemetry {
+                            telemetry.peers.update(self.allocated.peers.load(Ordering::Relaxed))
+                        }
+                        Ok(Some(ret))
+                    }
+                )?;
+                if stored {
+                    if updated.get() {
+                        self.churn.updated.fetch_add(1, Ordering::Relaxed);
+                    } else {
+                        self.churn.added.fetch_add(1, Ordering::Relaxed);
+                        self.allocated.table_peers.fetch_add(1, Ordering::Relaxed);
+                        self.enforce_memory_limit(Some(&ret))
+                    }
+                    if let Some(persistence) = self.config.persistence.as_ref().filter(|_| persist) {
+                        let peer = peer.clone();
+                        Self::spawn_persist(
+                            persistence, 
+                            move |persistence| if let Err(e) = persistence.save_node(&peer) {
+                                log::warn!(target: TARGET_TABLE, "Cannot persist DHT peer: {}", e)
+                            }
+                        )
+                    }
                 }
-                // Add more tasks if required 
-                if !all || (ret.len() < limit) || finished {
-                    break
-                }
             }
-            // Stop if possible 
-            if (all && (ret.len() >= limit)) || (!all && !ret.is_empty()) || finished {
-                break
-            } 
+        } else {
+            self.set_good_peer(&ret)
         }
-        if iter.order.is_empty() {
-            iter_opt.take();
+        Ok(Some(ret))
+    }
+
+    fn insert_value(&self, value: DhtValue, persist: bool) -> Result<bool> {
+        let dht_key_id = self.value_key_id(&value.key.key)?;
+        if value.ttl <= Version::get() {
+            fail!("Ignore expired DHT value with key {}", base64_encode(&dht_key_id))
+        }
+        let stored = match value.key.update_rule {
+            UpdateRule::Dht_UpdateRule_Signature => 
+                self.process_store_signed_value(dht_key_id, value.clone())?,
+            UpdateRule::Dht_UpdateRule_OverlayNodes =>
+                self.process_store_overlay_nodes(dht_key_id, value.clone())?,
+            _ => fail!("Unsupported store value {:?}", value)  
+        };
+        if stored {
+            if let Some(persistence) = self.config.persistence.as_ref().filter(|_| persist) {
+                Self::spawn_persist(
+                    persistence, 
+                    move |persistence| if let Err(e) = persistence.save_value(&value) {
+                        log::warn!(target: TARGET_STORE, "Cannot persist DHT value: {}", e)
+                    }
+                )
+            }
+        }
+        Ok(stored)
+    }
+
+    async fn is_address_published(dht: &Arc<Self>, key_id: &Arc<KeyId>) -> Result<bool> {
+        let key_id = Arc::new(dht.value_key_id(&Self::dht_key_from_key_id(key_id, "address"))?);
+        // Remote peers only: local copy does not prove publication
+        let options = SearchOptions {
+            all: false,
+            budget: &mut None,
+            policy: &DhtSearchPolicy::FullSearch(Self::MAX_TASKS),
+            strict: true,
+            trace: None
+        };
+        let found = DhtNode::find_value_with_peers(
+            dht,
+            &key_id,
+            |object| object.is::<AddressListBoxed>(),
+            options,
+            &mut None
+        ).await?;
+        Ok(!found.is_empty())
+    }
+
+    fn inflate(data: &[u8]) -> Result<Vec<u8>> {
+        let mut ret = Vec::new();
+        DeflateDecoder::new(data)
+            .take(Self::MAX_INFLATED_SIZE + 1)
+            .read_to_end(&mut ret)?;
+        if ret.len() as u64 > Self::MAX_INFLATED_SIZE {
+            fail!("Inflated DHT value exceeds {} bytes", Self::MAX_INFLATED_SIZE)
         }
         Ok(ret)
     }
 
-    fn get_affinity(key1: &DhtKeyId, key2: &DhtKeyId) -> u8 {
-        let mut ret = 0;
-        for i in 0..32 {
-            match key1[i] ^ key2[i] {
-                0 => ret += 8,
-                x => {
-                    if (x & 0xF0) == 0 {
-                        ret += Self::BITS[(x & 0x0F) as usize] + 4
-                    } else {
-                        ret += Self::BITS[(x >> 4) as usize]
-                    }
-                    break
-                }
+    fn is_own_key(&self, key_id: &Arc<KeyId>) -> bool {
+        key_id == self.node_key.id()
+    }
+
+    fn is_peer_in_bucket(&self, affinity: u8, peer: &Arc<KeyId>) -> bool {
+        if let Some(bucket) = self.buckets.get(&affinity) {
+            bucket.val().get(peer).is_some()
+        } else {
+            false
+        }
+    }
+
+    fn is_peer_outdated_in_bucket(&self, affinity: u8, peer_id: &Arc<KeyId>, peer: &Node) -> bool {
+        let Some(bucket) = self.buckets.get(&affinity) else {
+            return false
+        };
+        let Some(old_node) = bucket.val().get(peer_id) else {
+            return false
+        };
+        let old_node = &old_node.val().object;
+        (old_node.version < peer.version) || 
+        ((old_node.version == peer.version) && (old_node.addr_list != peer.addr_list))
+    }
+
+    fn make_room_for_peer(&self, affinity: u8) -> bool {
+        if self.allocated.table_peers.load(Ordering::Relaxed) < self.config.max_table_peers as u64 {
+            return true
+        }
+        let Some((evict_affinity, evict, score)) = self.find_eviction_candidate(None) else {
+            return true
+        };
+        if (evict_affinity >= affinity) && (score < Self::MAX_FAIL_COUNT) {
+            // No failing peer in table, and new one is not closer than the farthest one
+            return false
+        }
+        self.evict_peer(evict_affinity, &evict);
+        true
+    }
+
+    fn observe_query(&self, peers: &AdnlPeers, tag: u32) {
+        if let Some(observer) = &self.config.query_observer {
+            observer(peers.other(), tag)
+        }
+    }
+
+    // Compressed and plain lists are told apart by DHT key name
+    fn overlay_nodes_compression(
+        overlay_short_id: &Arc<OverlayShortId>, 
+        key: &DhtKey
+    ) -> Result<bool> {
+        for compressed in [false, true] {
+            let name = Self::overlay_nodes_name(compressed);
+            if &Self::dht_key_from_key_id(overlay_short_id, name) == key {
+                return Ok(compressed)
             }
         }
-        ret
+        fail!("Wrong DHT key for OverlayNodes")
+    }
+
+    fn overlay_nodes_name(compressed: bool) -> &'static str {
+        if compressed {
+            "nodes.deflate"
+        } else {
+            "nodes"
+        }
+    }
+
+    fn overlay_value_name(overlay_id: &Arc<OverlayShortId>, name: &str) -> String {
+        format!("overlay.{}.{}", base64_encode(overlay_id.data()), name)
     }
 
     fn parse_value_as_address(
@@ -965,7 +4352,7 @@ impl DhtNode {
     }
 
     fn process_find_node(&self, query: &FindNode) -> Result<Nodes> {
-        log::trace!(target: TARGET, "Process FindNode query {:?}", query);
+        log::trace!(target: TARGET_SEARCH, "Process FindNode query {:?}", query);
         let key1 = self.node_key.id().data();
         let key2 = query.key.as_slice();
         let mut dist = 0u8;
@@ -1003,12 +4390,43 @@ impl DhtNode {
         let ret = Nodes {
             nodes: ret.into()
         };
-        log::trace!(target: TARGET, "FindNode result {:?}", ret);
+        log::trace!(target: TARGET_SEARCH, "FindNode result {:?}", ret);
         Ok(ret)
     }
 
+    // Value as peer holds it, tombstone included, unlike search which skips tombstones
+    async fn probe_value(&self, peer: &Arc<KeyId>, key: &DhtKeyId) -> Result<Option<DhtValue>> {
+        let query = TaggedTlObject {
+            object: TLObject::new(
+                FindValue { 
+                    key: UInt256::from_slice(&key[..]),
+                    k: 6 
+                }
+            ),
+            #[cfg(feature = "telemetry")]
+            tag: self.tag_find_value
+        };
+        let Some(answer) = self.query(peer, &query).await? else {
+            fail!("No answer from DHT peer {}", peer)
+        };
+        let answer: DhtValueResult = Query::parse(answer, &query.object)?;
+        match answer {
+            DhtValueResult::Dht_ValueFound(value) => {
+                let value = value.value.only();
+                if &self.value_key_id(&value.key.key)? != key {
+                    fail!("Value from {} does not match DHT key ID {}", peer, base64_encode(key))
+                }
+                Ok(Some(value))
+            },
+            DhtValueResult::Dht_ValueNotFound(nodes) => {
+                self.add_gossip_peers(peer, &nodes.nodes.nodes)?;
+                Ok(None)
+            }
+        }
+    }
+
     fn process_find_value(&self, query: &FindValue) -> Result<DhtValueResult> {
-        log::trace!(target: TARGET, "Process FindValue query {:?}", query);
+        log::trace!(target: TARGET_SEARCH, "Process FindValue query {:?}", query);
         let ret = if let Some(value) = self.search_dht_key(query.key.as_slice()) {
             ValueFound {
                 value: value.into_boxed()
@@ -1016,11 +4434,18 @@ impl DhtNode {
         } else {
             ValueNotFound {
                 nodes: Nodes {
-                    nodes: self.get_known_nodes(query.k as usize)?.into()
+                    nodes: if self.config.private_mode_no_routing_hints {
+                        Vec::new().into()
+                    } else {
+                        self.get_known_nodes_ordered(
+                            (query.k.max(0) as usize).min(self.config.max_routing_hints), 
+                            self.config.known_nodes_order
+                        )?.into()
+                    }
                 }
             }.into_boxed()
         };
-        log::trace!(target: TARGET, "FindValue result {:?}", ret);
+        log::trace!(target: TARGET_SEARCH, "FindValue result {:?}", ret);
         Ok(ret)
     }
 
@@ -1029,40 +4454,39 @@ impl DhtNode {
     }
 
     fn process_store(&self, query: Store) -> Result<Stored> {
-        let dht_key_id = hash(query.value.key.key.clone())?;
-        if query.value.ttl <= Version::get() {
-            fail!("Ignore expired DHT value with key {}", base64_encode(&dht_key_id))
+        let Some(store_queue) = &self.store_queue else {
+            self.accept_value(query.value)?;
+            return Ok(Stored::Dht_Stored)
+        };
+        // Queued value is acknowledged before verification, full queue refuses the store
+        match store_queue.try_send(query.value) {
+            Ok(()) => {
+                let depth = self.allocated.store_queue.fetch_add(1, Ordering::Relaxed) + 1;
+                #[cfg(feature = "telemetry")]
+                if let Some(telemetry) = &self.telemetry {
+                    telemetry.store_queue.update(depth)
+                }
+                log::trace!(target: TARGET_STORE, "Queued DHT store, queue depth {}", depth);
+                Ok(Stored::Dht_Stored)
+            },
+            Err(mpsc::error::TrySendError::Full(_)) => fail!("DHT store queue is full, busy"),
+            Err(mpsc::error::TrySendError::Closed(_)) => fail!("DHT store queue is closed")
         }
-        match query.value.key.update_rule {
-            UpdateRule::Dht_UpdateRule_Signature => 
-                self.process_store_signed_value(dht_key_id, query.value)?,
-            UpdateRule::Dht_UpdateRule_OverlayNodes =>
-                self.process_store_overlay_nodes(dht_key_id, query.value)?,
-            _ => fail!("Unsupported store query {:?}", query)  
-        };                                                                                                                         
-        Ok(Stored::Dht_Stored)
     }
 
     fn process_store_overlay_nodes(&self, dht_key_id: DhtKeyId, value: DhtValue) -> Result<bool> {
-        log::trace!(target: TARGET, "Process Store Overlay Nodes {:?}", value);
-        if !value.signature.is_empty() {
-            fail!("Wrong value signature for OverlayNodes")
-        }
-        if !value.key.signature.is_empty() {
-            fail!("Wrong key signature for OverlayNodes")
-        }
-        let overlay_short_id = match value.key.id {
-            PublicKey::Pub_Overlay(_) => OverlayShortId::from_data(hash_boxed(&value.key.id)?),
-            _ => fail!("Wrong key description format for OverlayNodes")
-        };
-        if Self::dht_key_from_key_id(&overlay_short_id, "nodes") != value.key.key {
-            fail!("Wrong DHT key for OverlayNodes")
+        log::trace!(target: TARGET_STORE, "Process Store Overlay Nodes {:?}", value);
+        let (overlay_short_id, compressed) = Self::verify_overlay_nodes_key(&value)?;
+        if let Some(allowlist) = &self.config.overlay_allowlist {
+            if !allowlist.is_empty() && !allowlist.contains(&overlay_short_id) {
+                fail!("Overlay {} is not hosted by this node", overlay_short_id)
+            }
         }
-        let mut nodes_list = Self::deserialize_overlay_nodes(&value.value)?;
+        let mut nodes_list = Self::deserialize_overlay_nodes(&value.value, compressed)?;
         let mut nodes = Vec::new();
         while let Some(node) = nodes_list.pop() {
             if let Err(e) = OverlayUtils::verify_node(&overlay_short_id, &node) {
-                log::warn!(target: TARGET, "Bad overlay node {:?}: {}", node, e)
+                log::warn!(target: TARGET_STORE, "Bad overlay node {:?}: {}", node, e)
             } else {
                 nodes.push(node)
             }
@@ -1070,10 +4494,12 @@ impl DhtNode {
         if nodes.is_empty() {
             fail!("Empty overlay nodes list")
         }
-        add_counted_object_to_map_with_update(
+        let sizes = std::cell::Cell::new((0, 0));
+        let stored = add_counted_object_to_map_with_update(
             &self.storage,
             dht_key_id, 
             |old_value| {
+                let old_size = old_value.map_or(0, |old_value| Self::value_size(&old_value.object));
                 let old_value = if let Some(old_value) = old_value {
                     if old_value.object.ttl < Version::get() {
                         None
@@ -1086,7 +4512,7 @@ impl DhtNode {
                     None
                 };
                 let mut old_nodes = if let Some(old_value) = old_value {
-                    Self::deserialize_overlay_nodes(old_value)?
+                    Self::deserialize_overlay_nodes(old_value, compressed)?
                 } else {
                     Vec::new()
                 };
@@ -1107,49 +4533,62 @@ impl DhtNode {
                         old_nodes.push(node.clone())
                     }
                 }
-                let nodes = OverlayNodes {
-                    nodes: old_nodes.into()
-                }.into_boxed();
                 let mut ret = ValueObject {
                     object: value.clone(),
                     counter: self.allocated.values.clone().into()
                 };
                 #[cfg(feature = "telemetry")]
-                self.telemetry.values.update(
-                    self.allocated.values.load(Ordering::Relaxed)
-                );
-                ret.object.value = serialize_boxed(&nodes)?.into();
-                log::trace!(target: TARGET, "Store Overlay Nodes result {:?}", ret.object);
+                if let Some(telemetry) = &self.telemetry {
+                    telemetry.values.update(self.allocated.values.load(Ordering::Relaxed))
+                }
+                ret.object.value = Self::serialize_overlay_nodes(old_nodes, compressed)?.into();
+                log::trace!(target: TARGET_STORE, "Store Overlay Nodes result {:?}", ret.object);
+                sizes.set((old_size, Self::value_size(&ret.object)));
                 Ok(Some(ret))
             }
-        )
+        )?;
+        if stored {
+            self.account_value_size(sizes.get())
+        }
+        Ok(stored)
     }
 
     fn process_store_signed_value(
         &self, dht_key_id: DhtKeyId, 
         mut value: DhtValue
     ) -> Result<bool> {
+        if self.value_key_id(&value.key.key)? != dht_key_id {
+            fail!("DHT key ID {} does not match signed value key", base64_encode(&dht_key_id))
+        }
         self.verify_value(&mut value)?;
-        add_counted_object_to_map_with_update(
+        let sizes = std::cell::Cell::new((0, 0));
+        let stored = add_counted_object_to_map_with_update(
             &self.storage,
             dht_key_id, 
             |old_value| {
+                let mut old_size = 0;
                 if let Some(old_value) = old_value {
                     if old_value.object.ttl >= value.ttl {
                         return Ok(None)
                     }
+                    old_size = Self::value_size(&old_value.object);
                 }
                 let ret = ValueObject {
                     object: value.clone(),
                     counter: self.allocated.values.clone().into()
                 };
                 #[cfg(feature = "telemetry")]
-                self.telemetry.values.update(
-                    self.allocated.values.load(Ordering::Relaxed)
-                );
+                if let Some(telemetry) = &self.telemetry {
+                    telemetry.values.update(self.allocated.values.load(Ordering::Relaxed))
+                }
+                sizes.set((old_size, Self::value_size(&ret.object)));
                 Ok(Some(ret))
             }
-        )
+        )?;
+        if stored {
+            self.account_value_size(sizes.get())
+        }
+        Ok(stored)
     }
 
     async fn query(
@@ -1158,22 +4597,149 @@ impl DhtNode {
         query: &TaggedTlObject
     ) -> Result<Option<TLObject>> {
         let peers = AdnlPeers::with_keys(self.node_key.id().clone(), dst.clone());
-        let result = self.adnl.clone().query(query, &peers, None).await?;
-        self.set_query_result(result, dst)
+        match self.adnl.clone().query(query, &peers, None).await {
+            Ok(result) => self.set_query_result(result, dst),
+            Err(e) => {
+                self.set_query_error(dst)?;
+                Err(e)
+            }
+        }
     } 
 
+    async fn query_dht_nodes(&self, dst: &Arc<KeyId>) -> Result<Option<Vec<Node>>> {
+        self.query_dht_nodes_toward(dst, self.node_key.id().data()).await
+    }
+
+    async fn query_dht_nodes_toward(
+        &self, 
+        dst: &Arc<KeyId>, 
+        key: &DhtKeyId
+    ) -> Result<Option<Vec<Node>>> {
+        let query = TaggedTlObject {
+            object: TLObject::new(
+                FindNode {
+                    key: UInt256::with_array(*key),
+                    k: 10
+                }
+            ),
+            #[cfg(feature = "telemetry")]
+            tag: self.tag_find_node
+        };
+        let answer = self.query_with_prefix(dst, &query).await?;
+        let answer: NodesBoxed = if let Some(answer) = answer {
+            Query::parse(answer, &query.object)?
+        } else {
+            return Ok(None)
+        };        
+        let src = answer.only().nodes.0;
+        log::debug!(target: TARGET_TABLE, "-------- Found DHT nodes:");
+        for node in src.iter() {
+            log::debug!(target: TARGET_TABLE, "{:?}", node);
+        }
+        self.add_gossip_peers(dst, &src)?;
+        Ok(Some(src))
+    }
+
+    async fn query_signed_node(
+        &self, 
+        dst: &Arc<KeyId>, 
+        timeout: Option<Duration>
+    ) -> Result<Option<Node>> {
+        let query = TaggedTlObject {
+            object: TLObject::new(GetSignedAddressList),
+            #[cfg(feature = "telemetry")]
+            tag: self.tag_get_signed_address_list
+        };
+        let answer = if let Some(timeout) = timeout {
+            match tokio::time::timeout(timeout, self.query_with_prefix(dst, &query)).await {
+                Ok(answer) => answer?,
+                Err(_) => self.set_query_result(None, dst)?
+            }
+        } else {
+            self.query_with_prefix(dst, &query).await?
+        };
+        let answer: NodeBoxed = if let Some(answer) = answer {
+            Query::parse(answer, &query.object)?
+        } else {
+            return Ok(None)
+        };
+        let node = answer.only();
+        // Self-description is trusted only when signed by queried peer itself
+        if let Err(e) = self.verify_other_node(&node) {
+            log::warn!(target: TARGET_TABLE, "Bad signed node from DHT peer {}: {}", dst, e);
+            return Ok(None)
+        }
+        let key: Arc<dyn KeyOption> = (&node.id).try_into()?;
+        if key.id() != dst {
+            log::warn!(target: TARGET_TABLE, "DHT peer {} sent signed node of {}", dst, key.id());
+            return Ok(None)
+        }
+        self.add_peer(&node)?;
+        Ok(Some(node))
+    }
+
     async fn query_with_prefix(
         &self, 
         dst: &Arc<KeyId>, 
         query: &TaggedTlObject
     ) -> Result<Option<TLObject>> {
         let peers = AdnlPeers::with_keys(self.node_key.id().clone(), dst.clone());
+        let prefix = self.query_prefix()?;
         let result = self.adnl.clone()
-            .query_with_prefix(Some(&self.query_prefix[..]), query, &peers, None)
-            .await?;
-        self.set_query_result(result, dst)
+            .query_with_prefix(Some(&prefix[..]), query, &peers, None)
+            .await;
+        match result {
+            Ok(result) => self.set_query_result(result, dst),
+            Err(e) => {
+                self.set_query_error(dst)?;
+                Err(e)
+            }
+        }
     } 
 
+    fn random_key_in_bucket(&self, affinity: u8) -> DhtKeyId {
+        // Keep common prefix with own key, flip next bit, randomize the rest
+        let mut ret = *self.node_key.id().data();
+        let mut rng = rand::thread_rng();
+        let byte = affinity as usize / 8;
+        let bit = 0x80u8 >> (affinity % 8);
+        let mask = bit - 1;
+        ret[byte] = (ret[byte] & !(bit | mask)) | (!ret[byte] & bit) | (rng.gen::<u8>() & mask);
+        rng.fill(&mut ret[byte + 1..]);
+        ret
+    }
+
+    fn register_search(
+        &self, 
+        key_id: &DhtKeyId, 
+        policy: &DhtSearchPolicy,
+        peers_queried: Arc<AtomicU64>
+    ) -> SearchGuard {
+        let id = self.search_seqno.fetch_add(1, Ordering::Relaxed);
+        let search = SearchEntry {
+            key_id: *key_id,
+            started_at: Instant::now(),
+            policy: policy.clone(),
+            peers_queried
+        };
+        self.searches.insert(id, search);
+        SearchGuard {
+            dht: self,
+            id
+        }
+    }
+
+    fn register_task(&self, kind: DhtTaskKind, task: tokio::task::JoinHandle<()>) -> DhtTaskHandle {
+        let id = self.task_seqno.fetch_add(1, Ordering::Relaxed);
+        let abort = task.abort_handle();
+        self.tasks.insert(id, (kind, abort.clone()));
+        DhtTaskHandle {
+            abort,
+            id,
+            kind
+        }
+    }
+
     fn search_dht_key(&self, key: &DhtKeyId) -> Option<DhtValue> { 
         let version = Version::get();
         if let Some(value) = self.storage.get(key) {
@@ -1187,6 +4753,65 @@ impl DhtNode {
         }
     }
 
+    fn serialize_overlay_nodes(nodes: Vec<OverlayNode>, compressed: bool) -> Result<Vec<u8>> {
+        let nodes = OverlayNodes {
+            nodes: nodes.into()
+        }.into_boxed();
+        let data = serialize_boxed(&nodes)?;
+        if compressed {
+            Self::deflate(&data)
+        } else {
+            Ok(data)
+        }
+    }
+
+    fn set_bad_peer(&self, peer: &Arc<KeyId>, penalty: u8) -> Result<()> {
+        self.query_failures.fetch_add(1, Ordering::Relaxed);
+        loop {
+            if let Some(count) = self.bad_peers.get(peer) {
+                count.val().touch();
+                let mut cnt = count.val().load(Ordering::Relaxed);
+                if self.protected_peers.get(peer).is_some() {
+                    // Never demote protected peer below usability
+                    let max = Self::MAX_FAIL_COUNT - 1;
+                    if let Ok(old) = count.val().fetch_update(
+                        Ordering::Relaxed, 
+                        Ordering::Relaxed,
+                        |cnt| (cnt < max).then(|| max.min(cnt.saturating_add(penalty)))
+                    ) {
+                        cnt = max.min(old.saturating_add(penalty))
+                    }
+                } else {
+                    // Clamp atomically, concurrent failures must not overshoot
+                    let max = Self::MAX_FAIL_SCORE;
+                    if let Ok(old) = count.val().fetch_update(
+                        Ordering::Relaxed, 
+                        Ordering::Relaxed,
+                        |cnt| (cnt < max).then(|| max.min(cnt.saturating_add(penalty)))
+                    ) {
+                        cnt = max.min(old.saturating_add(penalty));
+                        if (old < Self::MAX_FAIL_COUNT) && (cnt >= Self::MAX_FAIL_COUNT) {
+                            self.churn.demoted.fetch_add(1, Ordering::Relaxed);
+                        }
+                    }
+                }
+                log::info!(target: TARGET_TABLE, "Make DHT peer {} feel bad {}", peer, cnt);
+                break
+            }
+            if add_unbound_object_to_map(
+                &self.bad_peers,
+                peer.clone(),
+                || Ok(AuxEntry::with_value(AtomicU8::new(0)))
+            )? {
+                self.allocated.bad_peers.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        if self.allocated.bad_peers.load(Ordering::Relaxed) > self.config.max_bad_peers as u64 {
+            self.trim_aux_maps();
+        }
+        Ok(())
+    }
+
     fn set_good_peer(&self, peer: &Arc<KeyId>) {
         loop {
             if let Some(count) = self.bad_peers.get(peer) {
@@ -1200,7 +4825,7 @@ impl DhtNode {
                     ).is_err() {
                         continue
                     }
-                    log::info!(target: TARGET, "Make DHT peer {} feel good {}", peer, cnt - 1);
+                    log::info!(target: TARGET_TABLE, "Make DHT peer {} feel good {}", peer, cnt - 1);
                 }
             }
             break
@@ -1212,37 +4837,46 @@ impl DhtNode {
         result: Option<TLObject>, 
         peer: &Arc<KeyId>
     ) -> Result<Option<TLObject>> {
+        self.update_peer_stats(peer, result.is_some())?;
         if result.is_some() {
             self.set_good_peer(peer)
         } else {
-            loop {
-                if let Some(count) = self.bad_peers.get(peer) {
-                    let mut cnt = count.val().load(Ordering::Relaxed);
-                    if cnt <= Self::MAX_FAIL_COUNT {
-                        cnt = count.val().fetch_add(2, Ordering::Relaxed) + 2;
-                    }
-                    log::info!(target: TARGET, "Make DHT peer {} feel bad {}", peer, cnt);
-                    break
-                }
-                add_unbound_object_to_map(
-                    &self.bad_peers,
-                    peer.clone(),
-                    || Ok(AtomicU8::new(0))
-                )?;
-            }
+            self.set_bad_peer(peer, self.config.query_timeout_penalty)?
         }
         Ok(result)
     }
     
-    fn sign_key_description(name: &str, key: &Arc<dyn KeyOption>) -> Result<DhtKeyDescription> {
-        let key_description = DhtKeyDescription {
-            id: key.try_into()?,
-            key: Self::dht_key_from_key_id(key.id(), name),
-            signature: Default::default(),
-            update_rule: UpdateRule::Dht_UpdateRule_Signature
-        };
-        key_description.sign(key)
-    }    
+    fn set_helpful_peer(&self, peer: &Arc<KeyId>) -> Result<()> {
+        if add_unbound_object_to_map(
+            &self.helpful, 
+            peer.clone(), 
+            || Ok(AuxEntry::with_value(AtomicU64::new(0)))
+        )? {
+            let count = self.allocated.helpful.fetch_add(1, Ordering::Relaxed) + 1;
+            if count > self.config.max_helpful_peers as u64 {
+                self.trim_aux_maps();
+            }
+        }
+        if let Some(helpful) = self.helpful.get(peer) {
+            helpful.val().touch();
+            let now = Version::get();
+            helpful.val().fetch_update(
+                Ordering::Relaxed,
+                Ordering::Relaxed,
+                |packed| {
+                    let score = Self::helpful_score(packed, now) + 1;
+                    let score = score.min(u32::MAX as u64);
+                    Some(((now as u32 as u64) << 32) | score)
+                }
+            ).ok();
+        }
+        Ok(())
+    }
+
+    fn set_query_error(&self, peer: &Arc<KeyId>) -> Result<()> {
+        self.update_peer_stats(peer, false)?;
+        self.set_bad_peer(peer, self.config.query_error_penalty)
+    }
 
     fn sign_local_node(&self) -> Result<Node> {
         let local_node = Node {
@@ -1255,13 +4889,86 @@ impl DhtNode {
     }
 
     fn sign_value(name: &str, value: Vec<u8>, key: &Arc<dyn KeyOption>) -> Result<DhtValue> {
-        let value = DhtValue {
-            key: Self::sign_key_description(name, key)?,
-            ttl: Version::get() + Self::TIMEOUT_VALUE,
-            signature: Default::default(),
-            value: value.into()
-        };
-        value.sign(key)
+        DhtValueBuilder::with_key_id(key.id(), name).with_value(value).build_signed(key)
+    }
+
+    // Backend does blocking IO, so it is run off async executor when called on one. 
+    // Records may be written out of order then, which load tolerates by version and ttl rules
+    fn spawn_persist(
+        persistence: &Arc<dyn DhtPersistence>, 
+        save: impl FnOnce(&dyn DhtPersistence) + Send + 'static
+    ) {
+        let persistence = persistence.clone();
+        let save = move || save(persistence.as_ref());
+        match tokio::runtime::Handle::try_current() {
+            Ok(runtime) => {
+                runtime.spawn_blocking(save);
+            },
+            Err(_) => save()
+        }
+    }
+
+    fn spawn_value_query<R: Into<DhtValueCheck>>(
+        dht: &Arc<Self>,
+        peer: Arc<KeyId>,
+        query: &ValueQuery,
+        check: impl Fn(&TLObject) -> R + Copy + Send + 'static,
+        wait: &Arc<Wait<FoundValue>>,
+        tasks: &mut SearchTasks
+    ) -> usize {
+        let dht = dht.clone();
+        let query = query.clone(); 
+        let wait = wait.clone(); 
+        let reqs = wait.request_immediate(); 
+        let task = tokio::spawn(
+            async move {
+                let ValueQuery { key_id, query, strict } = query;
+                match dht.value_query(&peer, &query, &key_id, check, strict).await {
+                    Ok(found) => wait.respond(found),
+                    Err(e) => {
+                        log::warn!(target: TARGET_SEARCH, "ERROR: {}", e);
+                        wait.respond(None)
+                    }
+                } 
+            } 
+        );
+        tasks.0.push(task);
+        reqs
+    }
+
+    fn start_store_worker(dht: &Arc<Self>, mut reader: mpsc::Receiver<DhtValue>) -> Result<()> {
+        let runtime = tokio::runtime::Handle::try_current().map_err(
+            |_| error!("DHT store queue needs Tokio runtime for its worker")
+        )?;
+        let dht = Arc::downgrade(dht);
+        runtime.spawn(
+            async move {
+                while let Some(value) = reader.recv().await {
+                    let Some(dht) = dht.upgrade() else {
+                        break
+                    };
+                    dht.allocated.store_queue.fetch_sub(1, Ordering::Relaxed);
+                    if let Err(e) = dht.accept_value(value) {
+                        log::debug!(target: TARGET_STORE, "Queued DHT store rejected: {}", e)
+                    }
+                }
+            }
+        );
+        Ok(())
+    }
+
+    // Keys are copied out so no map guard is held across yields in long scans
+    fn storage_keys(&self) -> Vec<DhtKeyId> {
+        self.storage.iter().map(|value| *value.key()).collect()
+    }
+
+    fn store_own_value(&self, key_id: DhtKeyId, value: DhtValue) -> Result<bool> {
+        self.published.insert(key_id, ());
+        match value.key.update_rule {
+            UpdateRule::Dht_UpdateRule_OverlayNodes => 
+                self.process_store_overlay_nodes(key_id, value),
+            _ => self.process_store_signed_value(key_id, value)
+        }
     }
 
     async fn store_value(
@@ -1271,8 +4978,24 @@ impl DhtNode {
         check_type: impl Fn(&TLObject) -> bool + Copy + Send + 'static,
         check_all: bool,
         check_vals: impl Fn(Vec<(DhtKeyDescription, TLObject)>) -> Result<bool>
-    ) -> Result<bool> {
-        let key_id = Arc::new(hash(key)?);
+    ) -> Result<bool> {
+        let outcome = Self::store_value_with_outcome(
+            dht, key, value, check_type, check_all, check_vals
+        ).await?;
+        Ok(outcome.is_durable())
+    }
+
+    async fn store_value_with_outcome(
+        dht: &Arc<Self>, 
+        key: DhtKey, 
+        value: DhtValue,
+        check_type: impl Fn(&TLObject) -> bool + Copy + Send + 'static,
+        check_all: bool,
+        check_vals: impl Fn(Vec<(DhtKeyDescription, TLObject)>) -> Result<bool>
+    ) -> Result<DhtStoreOutcome> {
+        let quorum = dht.config.store_quorum.max(1);
+        let mut confirmed = HashSet::new();
+        let key_id = Arc::new(dht.value_key_id(&key)?);
         let query = TaggedTlObject {
             object: TLObject::new(
                 Store {
@@ -1283,36 +5006,38 @@ impl DhtNode {
             tag: dht.tag_store
         };
         let query = Arc::new(query);
-        let policy = DhtSearchPolicy::FullSearch(Self::MAX_TASKS);
+        // Readback collects up to search limit values, so limit must cover quorum
+        let policy = DhtSearchPolicy::FullSearch(Self::MAX_TASKS.max(quorum as u8));
         let mut iter = None;
         let mut peer = dht.get_known_peer(&mut iter);
-        while peer.is_some() {
+        // Less reliable peers are stored to only if reliable ones do not make quorum
+        let mut deferred = Vec::new();
+        while peer.is_some() || !deferred.is_empty() {
             let (wait, mut queue_reader) = Wait::new();
+            let mut wave = Vec::new();
             while let Some(next) = peer {
                 peer = dht.get_known_peer(&mut iter);
+                if dht.config.reliable_store_first && !dht.is_reliable_peer(&next) {
+                    deferred.push(next)
+                } else {
+                    wave.push(next)
+                }
+            }
+            if wave.is_empty() {
+                wave = std::mem::take(&mut deferred)
+            }
+            for next in wave {
                 let dht = dht.clone();  
                 let query = query.clone();
                 let wait = wait.clone();
                 wait.request();
                 tokio::spawn(
                     async move {
-                        let ret = match dht.query(&next, &query).await {
-                            Ok(Some(answer)) => {
-                                match Query::parse::<TLObject, Stored>(answer, &query.object) {
-                                    Ok(_) => Some(()), // Probably stored
-                                    Err(answer) => {
-                                        log::debug!(
-                                            target: TARGET, 
-                                            "Improper store reply: {:?}", 
-                                            answer
-                                        );
-                                        None
-                                    }
-                                }
-                            },
-                            Ok(None) => None, // No reply at all 
+                        let ret = match dht.store_with_query(&next, &query).await {
+                            Ok(true) => Some(()), // Probably stored
+                            Ok(false) => None, 
                             Err(e) => {
-                                log::warn!(target: TARGET, "Store error: {:?}", e);
+                                log::warn!(target: TARGET_STORE, "Store error: {:?}", e);
                                 None
                             }
                         };
@@ -1322,46 +5047,225 @@ impl DhtNode {
             }
             while wait.wait(&mut queue_reader, false).await.is_some() { 
             }
-            let vals = DhtNode::find_value(
-                dht, 
-                &key_id, 
-                check_type,
-                &policy, 
-                check_all, 
-                &mut None
-            ).await?;
-            if check_vals(vals)? {
-                return Ok(true)
+            // Readback is not strict: stored value is own and signed here, so found values
+            // are checked against it by caller instead of signature re-verification
+            if quorum == 1 {
+                // Remote peers only: local copy confirms nothing
+                let options = SearchOptions {
+                    all: check_all,
+                    budget: &mut None,
+                    policy: &policy,
+                    strict: false,
+                    trace: None
+                };
+                let vals = DhtNode::find_value_with_peers(
+                    dht, &key_id, check_type, options, &mut None
+                ).await?;
+                let vals = vals.into_iter().map(|found| (found.key, found.object)).collect();
+                if check_vals(vals)? {
+                    return Ok(DhtStoreOutcome { confirmed: 1, quorum })
+                }
+            } else {
+                // Confirm replica by replica, each peer counts once
+                let options = SearchOptions {
+                    all: true,
+                    budget: &mut None,
+                    policy: &policy,
+                    strict: false,
+                    trace: None
+                };
+                let vals = DhtNode::find_value_with_peers(
+                    dht, &key_id, check_type, options, &mut None
+                ).await?;
+                for found in vals {
+                    if confirmed.contains(&found.peer) {
+                        continue
+                    }
+                    if check_vals(vec![(found.key, found.object)])? {
+                        confirmed.insert(found.peer);
+                    }
+                }
+                if confirmed.len() >= quorum {
+                    return Ok(DhtStoreOutcome { confirmed: confirmed.len(), quorum })
+                }
             }
             peer = dht.get_known_peer(&mut iter);
         }
-        Ok(false)
+        log::debug!(
+            target: TARGET_STORE, 
+            "Store quorum not reached: {} of {} replicas confirmed", 
+            confirmed.len(), quorum
+        );
+        Ok(DhtStoreOutcome { confirmed: confirmed.len(), quorum })
+    }
+
+    async fn store_with_query(&self, peer: &Arc<KeyId>, query: &TaggedTlObject) -> Result<bool> {
+        let answer = {
+            let _permit = self.store_limiter.acquire().await;
+            self.query(peer, query).await?
+        };
+        let answer = if let Some(answer) = answer {
+            answer
+        } else {
+            // No reply at all
+            return Ok(false)
+        };
+        match Query::parse::<TLObject, Stored>(answer, &query.object) {
+            Ok(_) => Ok(true),
+            Err(answer) => {
+                log::debug!(target: TARGET_STORE, "Improper store reply: {:?}", answer);
+                Ok(false)
+            }
+        }
+    }
+
+    fn trim_peer_map<V>(
+        &self, 
+        map: &lockfree::map::Map<Arc<KeyId>, AuxEntry<V>>, 
+        count: &AtomicU64,
+        limit: usize
+    ) -> usize {
+        if count.load(Ordering::Relaxed) <= limit as u64 {
+            return 0
+        }
+        // Trim some below limit, so the sorting pass does not repeat on each insert
+        let target = (limit - limit / Self::AUX_TRIM_SHARE) as u64;
+        // Entries of peers gone from table first, then least recently updated ones
+        let mut candidates: Vec<_> = map.iter().filter_map(
+            |entry| {
+                let peer = entry.key();
+                if self.protected_peers.get(peer).is_some() {
+                    None
+                } else {
+                    Some((self.is_peer_in_table(peer), entry.val().seen(), peer.clone()))
+                }
+            }
+        ).collect();
+        candidates.sort_unstable_by_key(|(in_table, seen, _)| (*in_table, *seen));
+        let mut ret = 0;
+        for (_, _, peer) in candidates {
+            if count.load(Ordering::Relaxed) <= target {
+                break
+            }
+            if map.remove(&peer).is_some() {
+                count.fetch_sub(1, Ordering::Relaxed);
+                ret += 1
+            }
+        }
+        if ret > 0 {
+            log::debug!(target: TARGET_TABLE, "Trimmed {} DHT auxiliary map entries", ret);
+        }
+        ret
+    }
+
+    fn update_peer_stats(&self, peer: &Arc<KeyId>, success: bool) -> Result<()> {
+        if add_unbound_object_to_map(
+            &self.reliability, 
+            peer.clone(), 
+            || Ok(AuxEntry::with_value(PeerStats::default()))
+        )? {
+            let count = self.allocated.reliability.fetch_add(1, Ordering::Relaxed) + 1;
+            if count > self.config.max_peer_reliability as u64 {
+                self.trim_aux_maps();
+            }
+        }
+        if let Some(stats) = self.reliability.get(peer) {
+            stats.val().touch();
+            if success {
+                stats.val().successes.fetch_add(1, Ordering::Relaxed);
+            } else {
+                stats.val().failures.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        Ok(())
+    }
+
+    fn update_peer_rtt(&self, peer: &Arc<KeyId>, rtt: Duration) -> Result<()> {
+        let sample = rtt.as_micros().min(u64::MAX as u128) as u64;
+        if add_unbound_object_to_map(
+            &self.rtts, 
+            peer.clone(), 
+            || Ok(AuxEntry::with_value(AtomicU64::new(sample)))
+        )? {
+            let count = self.allocated.rtts.fetch_add(1, Ordering::Relaxed) + 1;
+            if count > self.config.max_peer_rtts as u64 {
+                self.trim_aux_maps();
+            }
+            return Ok(())
+        }
+        if let Some(ewma) = self.rtts.get(peer) {
+            ewma.val().touch();
+            // EWMA with 1/8 weight of the new sample
+            ewma.val().fetch_update(
+                Ordering::Relaxed, 
+                Ordering::Relaxed, 
+                |old| Some(old - old / 8 + sample / 8)
+            ).ok();
+        }
+        Ok(())
     }
 
-    async fn value_query(
+    async fn value_query<R: Into<DhtValueCheck>>(
         &self, 
         peer: &Arc<KeyId>, 
         query: &Arc<TaggedTlObject>,
         key: &Arc<DhtKeyId>,
-        check: impl Fn(&TLObject) -> bool
-    ) -> Result<Option<(DhtKeyDescription, TLObject)>> {
-        let answer = self.query(peer, query).await?;
+        check: impl Fn(&TLObject) -> R,
+        strict: bool
+    ) -> Result<Option<FoundValue>> {
+        let answer = {
+            let _permit = self.search_limiter.acquire().await;
+            self.query(peer, query).await?
+        };
         if let Some(answer) = answer {
             let answer: DhtValueResult = Query::parse(answer, &query.object)?;
             match answer {
                 DhtValueResult::Dht_ValueFound(value) => {
                     let value = value.value.only();
                     log::debug!(
-                        target: TARGET, 
+                        target: TARGET_SEARCH, 
                         "Found value for DHT key ID {}: {:?}/{:?}", 
                         base64_encode(&key[..]), value.key, value.value
                     );
-                    let object = deserialize_boxed(&value.value)?;
-                    if check(&object) {
-                        return Ok(Some((value.key, object)))
+                    // Value must be stored exactly at queried address, otherwise peer
+                    // may substitute another validly signed value
+                    if self.value_key_id(&value.key.key)? != **key {
+                        log::debug!(
+                            target: TARGET_SEARCH,
+                            "Value from {} does not match DHT key ID {}",
+                            peer, base64_encode(&key[..])
+                        );
+                        return Ok(None)
+                    }
+                    if value.value.is_empty() {
+                        log::debug!(target: TARGET_SEARCH, "Tombstone found, value retired");
+                        return Ok(None)
+                    }
+                    let object = Self::deserialize_value(&value)?;
+                    let decision = check(&object).into();
+                    if decision != DhtValueCheck::Reject {
+                        if strict {
+                            if let Err(e) = self.verify_found_value(&value) {
+                                log::debug!(
+                                    target: TARGET_SEARCH, 
+                                    "Improper value found for DHT key ID {}: {}", 
+                                    base64_encode(&key[..]), e
+                                );
+                                return Ok(None)
+                            }
+                        }
+                        self.set_helpful_peer(peer)?;
+                        let found = FoundValue {
+                            key: value.key,
+                            object,
+                            peer: peer.clone(),
+                            stop: decision == DhtValueCheck::AcceptAndStop,
+                            ttl: value.ttl
+                        };
+                        return Ok(Some(found))
                     } 
                     log::debug!(
-                        target: TARGET,
+                        target: TARGET_SEARCH,
                         "Improper value found, object {:?}", 
                         object
                     );
@@ -1369,18 +5273,16 @@ impl DhtNode {
                 DhtValueResult::Dht_ValueNotFound(nodes) => {
                     let nodes = nodes.nodes.nodes;
                     log::debug!(
-                        target: TARGET, 
+                        target: TARGET_SEARCH, 
                         "Value not found on {} for DHT key ID {}, suggested {} other nodes",
                         peer, base64_encode(&key[..]), nodes.len()
                     );
-                    for node in nodes.iter() {          
-                        self.add_peer(node)?;
-                    }
+                    self.add_gossip_peers(peer, &nodes)?;
                 }
             }
         } else {
             log::debug!(
-                target: TARGET, 
+                target: TARGET_SEARCH, 
                 "No answer from {} to FindValue with DHT key ID {} query", 
                 peer, base64_encode(&key[..])
             );
@@ -1388,18 +5290,88 @@ impl DhtNode {
         Ok(None) 
     }
 
+    fn value_size(value: &DhtValue) -> u64 {
+        let size = value.value.len() + value.signature.len() + 
+            value.key.key.name.len() + value.key.signature.len();
+        size as u64 + Self::MEMORY_VALUE_ENTRY
+    }
+
+    fn value_key_id(&self, key: &DhtKey) -> Result<DhtKeyId> {
+        if let Some(salt) = &self.config.key_salt {
+            let mut data = salt.clone();
+            serialize_boxed_inplace(&mut data, &key.clone().into_boxed())?;
+            Ok(sha256_digest(&data))
+        } else {
+            hash(key.clone())
+        }
+    }
+
+    fn verify_any_value(&self, value: &DhtValue) -> Result<()> {
+        match value.key.update_rule {
+            UpdateRule::Dht_UpdateRule_Signature => self.verify_value(&mut value.clone()),
+            UpdateRule::Dht_UpdateRule_OverlayNodes => {
+                let (overlay_short_id, compressed) = Self::verify_overlay_nodes_key(value)?;
+                for node in Self::deserialize_overlay_nodes(&value.value, compressed)? {
+                    OverlayUtils::verify_node(&overlay_short_id, &node)?
+                }
+                Ok(())
+            },
+            _ => fail!("Unsupported DHT value update rule {:?}", value.key.update_rule)
+        }
+    }
+
+    fn verify_found_value(&self, value: &DhtValue) -> Result<()> {
+        if value.ttl <= Version::get() + Self::MIN_TTL_LEFT {
+            fail!("DHT value is expired or about to expire")
+        }
+        self.verify_any_value(value)
+    }
+
     fn verify_other_node(&self, node: &Node) -> Result<()> {
         let other_key: Arc<dyn KeyOption> = (&node.id).try_into()?;
+        self.check_key_type(&other_key)?;
         let mut node = node.clone();
         node.verify(&other_key)
     }
 
+    fn verify_overlay_nodes_key(value: &DhtValue) -> Result<(Arc<OverlayShortId>, bool)> {
+        if !value.signature.is_empty() {
+            fail!("Wrong value signature for OverlayNodes")
+        }
+        if !value.key.signature.is_empty() {
+            fail!("Wrong key signature for OverlayNodes")
+        }
+        let overlay_short_id = match value.key.id {
+            PublicKey::Pub_Overlay(_) => OverlayShortId::from_data(hash_boxed(&value.key.id)?),
+            _ => fail!("Wrong key description format for OverlayNodes")
+        };
+        let compressed = Self::overlay_nodes_compression(&overlay_short_id, &value.key.key)?;
+        Ok((overlay_short_id, compressed))
+    }
+
+    fn verify_stored_value(&self, key_id: &DhtKeyId, value: &DhtValue) -> Result<()> {
+        if &self.value_key_id(&value.key.key)? != key_id {
+            fail!("DHT key ID does not match stored value key")
+        }
+        self.verify_any_value(value)
+    }
+
     fn verify_value(&self, value: &mut DhtValue) -> Result<()> {
         let other_key: Arc<dyn KeyOption> = (&value.key.id).try_into()?;
+        self.check_key_type(&other_key)?;
+        if value.key.key.id.as_slice() != other_key.id().data() {
+            fail!("DHT key does not match signing key {}", other_key.id())
+        }
         value.verify(&other_key)?;
         value.key.verify(&other_key)
     }
 
+    async fn yield_on_scan(i: usize) {
+        if (i > 0) && (i % Self::SCAN_YIELD_STEP == 0) {
+            tokio::task::yield_now().await
+        }
+    }
+
 }
 
 #[async_trait::async_trait]
@@ -1407,58 +5379,32 @@ impl Subscriber for DhtNode {
 
     #[cfg(feature = "telemetry")]
     async fn poll(&self, _start: &Arc<Instant>) {
-        self.telemetry.peers.update(self.allocated.peers.load(Ordering::Relaxed));
-        self.telemetry.values.update(self.allocated.values.load(Ordering::Relaxed));
+        let Some(telemetry) = &self.telemetry else {
+            return
+        };
+        telemetry.bad_peers.update(self.allocated.bad_peers.load(Ordering::Relaxed));
+        telemetry.disagreements.update(self.allocated.disagreements.load(Ordering::Relaxed));
+        telemetry.gossip.update(self.allocated.gossip.load(Ordering::Relaxed));
+        telemetry.helpful.update(self.allocated.helpful.load(Ordering::Relaxed));
+        telemetry.peers.update(self.allocated.peers.load(Ordering::Relaxed));
+        telemetry.peers_added.update(self.churn.added.load(Ordering::Relaxed));
+        telemetry.peers_demoted.update(self.churn.demoted.load(Ordering::Relaxed));
+        telemetry.peers_evicted.update(self.churn.evicted.load(Ordering::Relaxed));
+        telemetry.peers_updated.update(self.churn.updated.load(Ordering::Relaxed));
+        telemetry.memory.update(self.memory_usage());
+        telemetry.reliability.update(self.allocated.reliability.load(Ordering::Relaxed));
+        telemetry.rtts.update(self.allocated.rtts.load(Ordering::Relaxed));
+        telemetry.store_queue.update(self.allocated.store_queue.load(Ordering::Relaxed));
+        telemetry.values.update(self.allocated.values.load(Ordering::Relaxed));
     }
 
     async fn try_consume_query(
         &self, 
         object: TLObject, 
-        _peers: &AdnlPeers
+        peers: &AdnlPeers
     ) -> Result<QueryResult> {
-        let object = match object.downcast::<DhtPing>() {
-            Ok(query) => return QueryResult::consume(
-                self.process_ping(&query)?, 
-                #[cfg(feature = "telemetry")]
-                None
-            ),
-            Err(object) => object
-        };
-        let object = match object.downcast::<FindNode>() {
-            Ok(query) => return QueryResult::consume(
-                self.process_find_node(&query)?, 
-                #[cfg(feature = "telemetry")]
-                None
-            ),
-            Err(object) => object
-        };
-        let object = match object.downcast::<FindValue>() {
-            Ok(query) => return QueryResult::consume_boxed(
-                self.process_find_value(&query)?, 
-                #[cfg(feature = "telemetry")]
-                None
-            ),
-            Err(object) => object
-        };
-        let object = match object.downcast::<GetSignedAddressList>() {
-            Ok(_) => return QueryResult::consume(
-                self.get_signed_node()?, 
-                #[cfg(feature = "telemetry")]
-                None
-            ),
-            Err(object) => object
-        };
-        match object.downcast::<Store>() {
-            Ok(query) => QueryResult::consume_boxed(
-                self.process_store(query)?, 
-                #[cfg(feature = "telemetry")]
-                None
-            ),
-            Err(object) => {
-                log::warn!(target: TARGET, "Unexpected DHT query {:?}", object);
-                Ok(QueryResult::Rejected(object))
-            }        
-        }
+        self.check_query_source(&object, peers.other(), self.is_known_source(peers.other()))?;
+        self.consume_query(object, peers)
     }    
 
     async fn try_consume_query_bundle(
@@ -1476,8 +5422,12 @@ impl Subscriber for DhtNode {
                 return Ok(QueryResult::RejectedBundle(objects));
             }
         };  
+        // Source is judged before its own prefix could add it to routing table
+        let source: Arc<dyn KeyOption> = (&other_node.id).try_into()?;
+        let known = self.is_known_source(source.id());
+        self.check_query_source(&objects[0], source.id(), known)?;
         self.add_peer(&other_node)?;
-        let ret = self.try_consume_query(objects.remove(0), peers).await?;
+        let ret = self.consume_query(objects.remove(0), peers)?;
         if let QueryResult::Rejected(object) = ret {
             fail!("Unexpected DHT query {:?}", object);
         }
@@ -1485,3 +5435,508 @@ impl Subscriber for DhtNode {
     }    
 
 }
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use adnl::node::AdnlNodeConfig;
+    use std::sync::atomic::AtomicU16;
+    use ton_api::ton::{
+        adnl::id::short::Short as AdnlShortId, overlay::node::tosign::ToSign as OverlayNodeToSign
+    };
+    use ton_types::Ed25519KeyOption;
+
+    const KEY_TAG: usize = 1;
+
+    static PORT: AtomicU16 = AtomicU16::new(4190);
+
+    async fn test_adnl() -> Arc<AdnlNode> {
+        let ip = format!("127.0.0.1:{}", PORT.fetch_add(1, Ordering::Relaxed));
+        let (_, adnl_config) = AdnlNodeConfig::with_ip_address_and_private_key_tags(
+            &ip, 
+            vec![KEY_TAG]
+        ).unwrap();
+        AdnlNode::with_config(adnl_config).await.unwrap()
+    }
+
+    async fn test_dht(config: DhtConfig) -> Arc<DhtNode> {
+        DhtNode::with_adnl_node_and_config(test_adnl().await, KEY_TAG, config).unwrap()
+    }
+
+    // Started DHT nodes, each one knowing all others
+    async fn test_dht_network(configs: Vec<DhtConfig>) -> Vec<Arc<DhtNode>> {
+        let mut ret = Vec::new();
+        for config in configs {
+            let dht = test_dht(config).await;
+            AdnlNode::start(&dht.adnl, vec![dht.clone() as Arc<dyn Subscriber>]).await.unwrap();
+            ret.push(dht)
+        }
+        for dht in ret.iter() {
+            for other in ret.iter() {
+                if !Arc::ptr_eq(dht, other) {
+                    dht.add_peer(&other.get_signed_node().unwrap()).unwrap();
+                }
+            }
+        }
+        ret
+    }
+
+    fn test_find_value_query(dht: &DhtNode, key_id: &DhtKeyId) -> Arc<TaggedTlObject> {
+        let query = TaggedTlObject {
+            object: TLObject::new(
+                FindValue { 
+                    key: UInt256::from_slice(&key_id[..]),
+                    k: 6 
+                }
+            ),
+            #[cfg(feature = "telemetry")]
+            tag: dht.tag_find_value
+        };
+        Arc::new(query)
+    }
+
+    // Value with one validly signed node of given overlay
+    fn test_overlay_nodes_value(overlay_id: &OverlayId) -> (Arc<OverlayShortId>, DhtValue) {
+        let overlay_short_id = OverlayShortId::from_data(
+            hash(Overlay { name: overlay_id.to_vec().into() }).unwrap()
+        );
+        let key = Ed25519KeyOption::generate().unwrap();
+        let overlay = UInt256::with_array(*overlay_short_id.data());
+        let version = Version::get();
+        let to_sign = OverlayNodeToSign {
+            id: AdnlShortId {
+                id: UInt256::with_array(*key.id().data())
+            },
+            overlay: overlay.clone(),
+            version
+        }.into_boxed();
+        let node = OverlayNode {
+            id: (&key).try_into().unwrap(),
+            overlay,
+            version,
+            signature: key.sign(&serialize_boxed(&to_sign).unwrap()).unwrap().to_vec().into()
+        };
+        let value = DhtValueBuilder::with_key_id(&overlay_short_id, "nodes")
+            .with_value(DhtNode::serialize_overlay_nodes(vec![node], false).unwrap())
+            .build_overlay_nodes(overlay_id)
+            .unwrap();
+        (overlay_short_id, value)
+    }
+
+    fn test_overlay_nodes(count: usize) -> Vec<OverlayNode> {
+        let overlay = UInt256::rand();
+        (0..count).map(
+            |_| {
+                let key = Ed25519KeyOption::generate().unwrap();
+                OverlayNode {
+                    id: (&key).try_into().unwrap(),
+                    overlay: overlay.clone(),
+                    version: Version::get(),
+                    signature: key.sign(overlay.as_slice()).unwrap().to_vec().into()
+                }
+            }
+        ).collect()
+    }
+
+    #[test]
+    fn test_overlay_nodes_compression() {
+        for count in [1, 16, 256] {
+            let nodes = test_overlay_nodes(count);
+            let plain = DhtNode::serialize_overlay_nodes(nodes.clone(), false).unwrap();
+            let compressed = DhtNode::serialize_overlay_nodes(nodes.clone(), true).unwrap();
+            if count > 1 {
+                assert!(compressed.len() < plain.len())
+            }
+            assert_eq!(DhtNode::deserialize_overlay_nodes(&compressed, true).unwrap(), nodes);
+            assert!(DhtNode::deserialize_overlay_nodes(&compressed, false).is_err())
+        }
+    }
+
+    #[test]
+    fn test_inflate_limit() {
+        let data = vec![0u8; DhtNode::MAX_INFLATED_SIZE as usize + 1];
+        let compressed = DhtNode::deflate(&data).unwrap();
+        assert!(DhtNode::inflate(&compressed).is_err());
+        let compressed = DhtNode::deflate(&data[1..]).unwrap();
+        assert_eq!(DhtNode::inflate(&compressed).unwrap().len(), data.len() - 1)
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_fail_score_clamped_under_concurrency() {
+        let dht = test_dht(DhtConfig::default()).await;
+        let peer = test_dht(DhtConfig::default()).await.node_key.id().clone();
+        let mut tasks = Vec::new();
+        for i in 0..8 {
+            let dht = dht.clone();
+            let peer = peer.clone();
+            tasks.push(
+                tokio::spawn(
+                    async move {
+                        for j in 0..1000 {
+                            if (i + j) % 3 == 0 {
+                                dht.set_good_peer(&peer)
+                            } else {
+                                dht.set_bad_peer(&peer, 2).unwrap()
+                            }
+                            let score = dht.bad_peers.get(&peer).unwrap().val().load(
+                                Ordering::Relaxed
+                            );
+                            assert!(score <= DhtNode::MAX_FAIL_SCORE);
+                            if j % 64 == 0 {
+                                tokio::task::yield_now().await
+                            }
+                        }
+                    }
+                )
+            )
+        }
+        for task in tasks {
+            task.await.unwrap()
+        }
+        let score = dht.bad_peers.get(&peer).unwrap().val().load(Ordering::Relaxed);
+        assert!(score <= DhtNode::MAX_FAIL_SCORE);
+        // Failures only: score settles exactly at the clamp
+        for _ in 0..100 {
+            dht.set_bad_peer(&peer, u8::MAX).unwrap()
+        }
+        let score = dht.bad_peers.get(&peer).unwrap().val().load(Ordering::Relaxed);
+        assert_eq!(score, DhtNode::MAX_FAIL_SCORE)
+    }
+
+    #[tokio::test]
+    async fn test_routing_table_round_trip() {
+        let dht = test_dht(DhtConfig::default()).await;
+        let mut peers = HashSet::new();
+        for _ in 0..3 {
+            let other = test_dht(DhtConfig::default()).await;
+            let peer = dht.add_peer(&other.get_signed_node().unwrap()).unwrap().unwrap();
+            peers.insert(peer);
+        }
+        let path = std::env::temp_dir().join(
+            format!("dht_routing_table_{}", hex::encode(dht.node_key.id().data()))
+        );
+        assert_eq!(dht.save_routing_table(&path).unwrap(), peers.len());
+        let loaded = test_dht(DhtConfig::default()).await;
+        assert_eq!(loaded.load_routing_table(&path).unwrap(), peers.len());
+        let mut iter = None;
+        let mut loaded_peers = HashSet::new();
+        while let Some(peer) = loaded.get_known_peer(&mut iter) {
+            loaded_peers.insert(peer);
+        }
+        assert_eq!(loaded_peers, peers);
+        let data = std::fs::read(&path).unwrap();
+        let header_len = DhtNode::ROUTING_TABLE_MAGIC.len() + 4;
+        // Bad magic
+        let mut bad = data.clone();
+        bad[0] ^= 0xFF;
+        std::fs::write(&path, &bad).unwrap();
+        assert!(test_dht(DhtConfig::default()).await.load_routing_table(&path).is_err());
+        // Bumped version
+        let mut bad = data.clone();
+        bad[header_len - 4..header_len].copy_from_slice(
+            &(DhtNode::ROUTING_TABLE_VERSION + 1).to_le_bytes()
+        );
+        std::fs::write(&path, &bad).unwrap();
+        assert!(test_dht(DhtConfig::default()).await.load_routing_table(&path).is_err());
+        // Truncated header and truncated contents
+        for len in [header_len - 1, data.len() - 1] {
+            std::fs::write(&path, &data[..len]).unwrap();
+            let fresh = test_dht(DhtConfig::default()).await;
+            assert!(fresh.load_routing_table(&path).is_err());
+            assert_eq!(fresh.known_peers.count(), 0)
+        }
+        std::fs::remove_file(&path).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_own_node_never_queried() {
+        let dht = test_dht(DhtConfig::default()).await;
+        let own = dht.node_key.id().clone();
+        assert!(dht.add_peer(&dht.get_signed_node().unwrap()).unwrap().is_none());
+        assert_eq!(dht.known_peers.count(), 0);
+        let other = test_dht(DhtConfig::default()).await;
+        let peer = dht.add_peer(&other.get_signed_node().unwrap()).unwrap().unwrap();
+        let key = DhtNode::dht_key_from_key_id(&own, "address");
+        let key_id = Arc::new(dht.value_key_id(&key).unwrap());
+        let iter = DhtIterator::with_key_id(&dht, key_id.clone());
+        assert!(iter.order.iter().all(|(_, queried)| queried != &own));
+        let iter = DhtIterator::with_peers(&dht, key_id, vec![own.clone(), peer.clone(), own]);
+        let queried: Vec<_> = iter.order.into_iter().map(|(_, queried)| queried).collect();
+        assert_eq!(queried, vec![peer])
+    }
+
+    #[tokio::test]
+    async fn test_tombstone_store_quorum() {
+        let config = DhtConfig {
+            store_quorum: 2,
+            ..Default::default()
+        };
+        let nodes = test_dht_network(
+            vec![config, DhtConfig::default(), DhtConfig::default()]
+        ).await;
+        let key = Ed25519KeyOption::generate().unwrap();
+        // Replicas holding tombstone answer with no value, yet count to quorum
+        assert!(DhtNode::store_tombstone(&nodes[0], &key, "test").await.unwrap());
+        let key_id = DhtNode::dht_key_from_key_id(key.id(), "test");
+        let key_id = nodes[0].value_key_id(&key_id).unwrap();
+        for replica in &nodes[1..] {
+            assert!(replica.search_dht_key(&key_id).unwrap().value.is_empty())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_unknown_source_refused_after_ping() {
+        let mut config = DhtConfig::default();
+        config.source_policy.store = true;
+        let dht = test_dht(config).await;
+        let other = test_dht(DhtConfig::default()).await;
+        let peers = AdnlPeers::with_keys(dht.node_key.id().clone(), other.node_key.id().clone());
+        let prefix = || deserialize_boxed(&other.query_prefix().unwrap()).unwrap();
+        let ping = TLObject::new(DhtPing { random_id: 1 });
+        dht.try_consume_query_bundle(vec![prefix(), ping], &peers).await.unwrap();
+        assert!(dht.is_peer_in_table(other.node_key.id()));
+        let value = DhtNode::sign_value("test", vec![1, 2, 3], &other.node_key).unwrap();
+        let key_id = dht.value_key_id(&value.key.key).unwrap();
+        let store = TLObject::new(Store { value });
+        match dht.try_consume_query_bundle(vec![prefix(), store], &peers).await {
+            Err(e) => assert!(e.to_string().contains("unknown source")),
+            Ok(_) => panic!("Store from unknown source served")
+        }
+        assert!(dht.search_dht_key(&key_id).is_none())
+    }
+
+    #[tokio::test]
+    async fn test_bundle_source_judged_by_prefix() {
+        let other = test_dht(DhtConfig::default()).await;
+        let mut config = DhtConfig::default();
+        config.source_policy.store = true;
+        config.source_policy.allowlist.insert(other.node_key.id().clone());
+        let dht = test_dht(config).await;
+        // ADNL sender differs from allowlisted node of prefix
+        let sender = Ed25519KeyOption::generate().unwrap();
+        let peers = AdnlPeers::with_keys(dht.node_key.id().clone(), sender.id().clone());
+        let prefix = deserialize_boxed(&other.query_prefix().unwrap()).unwrap();
+        let pong = serialize_boxed(&DhtPong { random_id: 1 }.into_boxed()).unwrap();
+        let value = DhtNode::sign_value("test", pong, &other.node_key).unwrap();
+        let key_id = dht.value_key_id(&value.key.key).unwrap();
+        let store = TLObject::new(Store { value });
+        dht.try_consume_query_bundle(vec![prefix, store], &peers).await.unwrap();
+        assert!(dht.search_dht_key(&key_id).is_some())
+    }
+
+    #[tokio::test]
+    async fn test_salted_key_spaces_separated() {
+        let salted = |salt: &[u8]| DhtConfig {
+            key_salt: Some(salt.to_vec()),
+            ..Default::default()
+        };
+        let nodes = test_dht_network(vec![salted(b"first"), salted(b"second")]).await;
+        let key = Ed25519KeyOption::generate().unwrap();
+        let pong = serialize_boxed(&DhtPong { random_id: 1 }.into_boxed()).unwrap();
+        let value = DhtNode::sign_value("test", pong, &key).unwrap();
+        let key_ids: Vec<_> = nodes.iter().map(
+            |dht| dht.value_key_id(&value.key.key).unwrap()
+        ).collect();
+        assert_ne!(key_ids[0], key_ids[1]);
+        assert!(nodes[0].store_own_value(key_ids[0], value.clone()).unwrap());
+        assert!(nodes[0].search_dht_key(&key_ids[0]).is_some());
+        // Value is neither accepted under key ID of other salt nor found by its node
+        assert!(nodes[1].store_own_value(key_ids[0], value).is_err());
+        for key_id in key_ids {
+            let query = test_find_value_query(&nodes[1], &key_id);
+            let found = nodes[1].value_query(
+                nodes[0].node_key.id(), &query, &Arc::new(key_id), |_| true, false
+            ).await.unwrap();
+            assert!(found.is_none())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_disallowed_key_type_rejected() {
+        // No key type has ID 0, so Ed25519 keys are not accepted
+        let config = DhtConfig {
+            accepted_key_types: Some([0].into_iter().collect()),
+            ..Default::default()
+        };
+        let dht = test_dht(config).await;
+        let other = test_dht(DhtConfig::default()).await;
+        assert!(dht.add_peer(&other.get_signed_node().unwrap()).unwrap().is_none());
+        assert!(!dht.is_peer_in_table(other.node_key.id()));
+        let pong = serialize_boxed(&DhtPong { random_id: 1 }.into_boxed()).unwrap();
+        let value = DhtNode::sign_value("test", pong, &other.node_key).unwrap();
+        let key_id = dht.value_key_id(&value.key.key).unwrap();
+        assert!(dht.process_store(Store { value }).is_err());
+        assert!(dht.search_dht_key(&key_id).is_none())
+    }
+
+    #[tokio::test]
+    async fn test_overlay_allowlist() {
+        let (allowed, allowed_value) = test_overlay_nodes_value(&rand::thread_rng().gen());
+        let (_, other_value) = test_overlay_nodes_value(&rand::thread_rng().gen());
+        let config = DhtConfig {
+            overlay_allowlist: Some([allowed].into_iter().collect()),
+            ..Default::default()
+        };
+        let dht = test_dht(config).await;
+        let key_id = dht.value_key_id(&allowed_value.key.key).unwrap();
+        dht.process_store(Store { value: allowed_value }).unwrap();
+        assert!(dht.search_dht_key(&key_id).is_some());
+        let key_id = dht.value_key_id(&other_value.key.key).unwrap();
+        assert!(dht.process_store(Store { value: other_value }).is_err());
+        assert!(dht.search_dht_key(&key_id).is_none())
+    }
+
+    #[tokio::test]
+    async fn test_local_node_refresh_advances_version() {
+        let dht = test_dht(DhtConfig::default()).await;
+        let version = || {
+            let prefix = deserialize_boxed(&dht.query_prefix().unwrap()).unwrap();
+            prefix.downcast::<DhtQuery>().unwrap().node.version
+        };
+        let before = version();
+        // Version is counted in seconds
+        tokio::time::sleep(Duration::from_millis(1100)).await;
+        dht.refresh_local_node().unwrap();
+        assert!(version() > before)
+    }
+
+    #[tokio::test]
+    async fn test_node_export_import_round_trip() {
+        let mut exported = Vec::new();
+        let mut peers = HashSet::new();
+        for _ in 0..3 {
+            let other = test_dht(DhtConfig::default()).await;
+            exported.push(format!("{}\n", other.export_node().unwrap()));
+            peers.insert(other.node_key.id().clone());
+        }
+        let fresh = test_dht(DhtConfig::default()).await;
+        for node in exported {
+            fresh.add_peer(&DhtNode::import_node(&node).unwrap()).unwrap().unwrap();
+        }
+        let mut iter = None;
+        let mut imported = HashSet::new();
+        while let Some(peer) = fresh.get_known_peer(&mut iter) {
+            imported.insert(peer);
+        }
+        assert_eq!(imported, peers);
+        assert!(DhtNode::import_node("not a node").is_err())
+    }
+
+    #[tokio::test]
+    async fn test_search_without_peers_fails() {
+        let config = DhtConfig {
+            fail_on_no_peers: true,
+            ..Default::default()
+        };
+        let dht = test_dht(config).await;
+        let key = Ed25519KeyOption::generate().unwrap();
+        let search = DhtNode::find_address(&dht, key.id());
+        match tokio::time::timeout(Duration::from_secs(5), search).await.unwrap() {
+            Err(e) => assert!(matches!(e.downcast_ref::<DhtError>(), Some(DhtError::NoPeers))),
+            Ok(_) => panic!("Search without peers did not fail")
+        }
+    }
+
+    #[tokio::test]
+    async fn test_file_persistence_compaction() {
+        let path = std::env::temp_dir().join(
+            format!("dht_persistence_{}", hex::encode(UInt256::rand().as_slice()))
+        );
+        let persistence = Arc::new(DhtFilePersistence::with_path(&path).unwrap());
+        let key = Ed25519KeyOption::generate().unwrap();
+        let pong = serialize_boxed(&DhtPong { random_id: 1 }.into_boxed()).unwrap();
+        let old = DhtNode::sign_value("test", pong.clone(), &key).unwrap();
+        let new = DhtValueBuilder::with_key_id(key.id(), "test")
+            .with_ttl(old.ttl + 1)
+            .with_value(pong)
+            .build_signed(&key)
+            .unwrap();
+        persistence.save_value(&old).unwrap();
+        persistence.append(&persistence.values, &[1, 2, 3]).unwrap();
+        persistence.save_value(&new).unwrap();
+        persistence.append(&persistence.nodes, &[1, 2, 3]).unwrap();
+        let config = DhtConfig {
+            persistence: Some(persistence.clone() as Arc<dyn DhtPersistence>),
+            ..Default::default()
+        };
+        let dht = test_dht(config).await;
+        assert_eq!(dht.load_persisted().unwrap(), (0, 2));
+        // Corrupt and superseded records are gone after load
+        assert!(persistence.load_nodes().unwrap().is_empty());
+        assert_eq!(persistence.load_values().unwrap(), vec![new]);
+        std::fs::remove_dir_all(&path).unwrap()
+    }
+
+    #[test]
+    fn test_store_queue_needs_runtime() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let adnl = runtime.block_on(test_adnl());
+        let config = || DhtConfig {
+            store_queue_capacity: Some(16),
+            ..Default::default()
+        };
+        assert!(DhtNode::with_adnl_node_and_config(adnl.clone(), KEY_TAG, config()).is_err());
+        let _guard = runtime.enter();
+        assert!(DhtNode::with_adnl_node_and_config(adnl, KEY_TAG, config()).is_ok())
+    }
+
+    #[tokio::test]
+    async fn test_failing_peer_evicted_first() {
+        let config = DhtConfig {
+            max_table_peers: 2,
+            ..Default::default()
+        };
+        let dht = test_dht(config).await;
+        let mut peers = Vec::new();
+        for _ in 0..2 {
+            let other = test_dht(DhtConfig::default()).await;
+            let peer = dht.add_peer(&other.get_signed_node().unwrap()).unwrap().unwrap();
+            let affinity = DhtNode::get_affinity(dht.node_key.id().data(), peer.data());
+            peers.push((affinity, peer))
+        }
+        peers.sort_by_key(|(affinity, _)| *affinity);
+        let (far, close) = (&peers[0], &peers[1]);
+        // Healthy table: farthest peer is the candidate, yet no newcomer at least as far 
+        // makes room
+        let (affinity, _, _) = dht.find_eviction_candidate(None).unwrap();
+        assert_eq!(affinity, far.0);
+        assert!(!dht.make_room_for_peer(0));
+        // Failing peer goes first even when closer than healthy ones
+        dht.set_bad_peer(&close.1, DhtNode::MAX_FAIL_COUNT).unwrap();
+        let (_, candidate, _) = dht.find_eviction_candidate(None).unwrap();
+        assert_eq!(candidate, close.1);
+        assert!(dht.make_room_for_peer(0));
+        assert!(!dht.is_peer_in_table(&close.1));
+        assert!(dht.is_peer_in_table(&far.1))
+    }
+
+    #[tokio::test]
+    async fn test_tag_store_outcomes_in_input_order() {
+        let dht = test_dht(DhtConfig::default()).await;
+        let unknown = KEY_TAG + 1;
+        let tags = [KEY_TAG, unknown, KEY_TAG];
+        let outcomes = DhtNode::store_ip_address_for_tags(&dht, &tags).await;
+        let order: Vec<_> = outcomes.iter().map(|(key_tag, _)| *key_tag).collect();
+        assert_eq!(order, tags);
+        assert!(outcomes[1].1.is_err())
+    }
+
+    #[tokio::test]
+    async fn test_storage_comparison_tombstone() {
+        let nodes = test_dht_network(vec![DhtConfig::default(), DhtConfig::default()]).await;
+        let key = Ed25519KeyOption::generate().unwrap();
+        let tombstone = DhtNode::sign_value("test", Vec::new(), &key).unwrap();
+        let key_id = nodes[0].value_key_id(&tombstone.key.key).unwrap();
+        for dht in &nodes {
+            assert!(dht.accept_value(tombstone.clone()).unwrap())
+        }
+        let diffs = nodes[0].compare_storage_with(nodes[1].node_key.id(), &[key_id]).await;
+        let (key, diff) = &diffs[0];
+        assert_eq!(key, &key_id);
+        let diff = diff.as_ref().unwrap();
+        assert_eq!(diff.remote_ttl, Some(tombstone.ttl));
+        assert!(diff.is_consistent())
+    }
+
+}